@@ -0,0 +1,22 @@
+CREATE TABLE jobs (
+    id SERIAL PRIMARY KEY NOT NULL,
+    layer_uuid VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,
+    error VARCHAR,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX jobs_layer_uuid ON jobs (layer_uuid);
+
+CREATE TABLE job_commands (
+    id SERIAL PRIMARY KEY NOT NULL,
+    job_id INTEGER NOT NULL REFERENCES jobs (id) ON DELETE CASCADE,
+    program VARCHAR NOT NULL,
+    exit_status INTEGER,
+    stdout TEXT NOT NULL,
+    stdout_truncated BOOLEAN NOT NULL,
+    stderr TEXT NOT NULL,
+    stderr_truncated BOOLEAN NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX job_commands_job_id ON job_commands (job_id);
@@ -0,0 +1,11 @@
+CREATE TABLE deliveries (
+    id SERIAL PRIMARY KEY NOT NULL,
+    layer_uuid VARCHAR NOT NULL,
+    beneficiary VARCHAR NOT NULL,
+    method VARCHAR NOT NULL,
+    state VARCHAR NOT NULL,
+    error VARCHAR,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX deliveries_layer_uuid ON deliveries (layer_uuid);
@@ -0,0 +1,23 @@
+CREATE TABLE audit_log (
+    id BIGSERIAL PRIMARY KEY NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    event VARCHAR NOT NULL,
+    layer_uuid VARCHAR,
+    actor VARCHAR NOT NULL,
+    details VARCHAR NOT NULL,
+    prev_hash VARCHAR NOT NULL,
+    hash VARCHAR NOT NULL UNIQUE,
+    signature VARCHAR
+);
+CREATE INDEX audit_log_layer_uuid ON audit_log (layer_uuid);
+
+-- append-only, entries can't be changed or removed without dropping the trigger
+CREATE FUNCTION audit_log_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
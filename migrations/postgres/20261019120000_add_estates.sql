@@ -0,0 +1,16 @@
+-- rows from before estates belong to the default estate
+ALTER TABLE shares ADD COLUMN estate VARCHAR NOT NULL DEFAULT 'default';
+DROP INDEX shares_layer_uuid_share;
+CREATE UNIQUE INDEX shares_estate_layer_uuid_share ON shares (estate, layer_uuid, share);
+
+ALTER TABLE jobs ADD COLUMN estate VARCHAR NOT NULL DEFAULT 'default';
+DROP INDEX jobs_layer_uuid;
+CREATE INDEX jobs_estate_layer_uuid ON jobs (estate, layer_uuid);
+
+ALTER TABLE deliveries ADD COLUMN estate VARCHAR NOT NULL DEFAULT 'default';
+DROP INDEX deliveries_layer_uuid;
+CREATE INDEX deliveries_estate_layer_uuid ON deliveries (estate, layer_uuid);
+
+-- every estate has its own hash chain
+ALTER TABLE audit_log ADD COLUMN estate VARCHAR NOT NULL DEFAULT 'default';
+CREATE INDEX audit_log_estate ON audit_log (estate, id);
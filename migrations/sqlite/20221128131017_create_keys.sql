@@ -0,0 +1,7 @@
+CREATE TABLE shares (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    layer_uuid TEXT NOT NULL,
+    share TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX shares_layer_uuid_share ON shares (layer_uuid, share);
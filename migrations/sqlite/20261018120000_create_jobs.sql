@@ -0,0 +1,22 @@
+CREATE TABLE jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    layer_uuid TEXT NOT NULL,
+    state TEXT NOT NULL,
+    error TEXT,
+    started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TEXT
+);
+CREATE INDEX jobs_layer_uuid ON jobs (layer_uuid);
+
+CREATE TABLE job_commands (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    job_id INTEGER NOT NULL REFERENCES jobs (id) ON DELETE CASCADE,
+    program TEXT NOT NULL,
+    exit_status INTEGER,
+    stdout TEXT NOT NULL,
+    stdout_truncated BOOLEAN NOT NULL,
+    stderr TEXT NOT NULL,
+    stderr_truncated BOOLEAN NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX job_commands_job_id ON job_commands (job_id);
@@ -0,0 +1,11 @@
+CREATE TABLE deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    layer_uuid TEXT NOT NULL,
+    beneficiary TEXT NOT NULL,
+    method TEXT NOT NULL,
+    state TEXT NOT NULL,
+    error TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TEXT
+);
+CREATE INDEX deliveries_layer_uuid ON deliveries (layer_uuid);
@@ -0,0 +1,23 @@
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    created_at TEXT NOT NULL,
+    event TEXT NOT NULL,
+    layer_uuid TEXT,
+    actor TEXT NOT NULL,
+    details TEXT NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL UNIQUE,
+    signature TEXT
+);
+CREATE INDEX audit_log_layer_uuid ON audit_log (layer_uuid);
+
+-- append-only, entries can't be changed or removed without dropping the triggers
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
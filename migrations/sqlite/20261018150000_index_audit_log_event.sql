@@ -0,0 +1,2 @@
+-- counted for metrics
+CREATE INDEX audit_log_event ON audit_log (event);
@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use chrono::{SubsecRound, Utc};
+use pgp::composed::{Deserializable, SignedSecretKey, StandaloneSignature};
+use pgp::crypto::hash::HashAlgorithm;
+use pgp::packet::{SignatureConfig, SignatureType, Subpacket, SubpacketData};
+use pgp::types::{KeyTrait, PublicKeyTrait};
+
+pub(crate) const MESSAGE_HEADER: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+const SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// Clearsigned OpenPGP message as defined by the cleartext signature framework (RFC 4880 §7)
+pub(crate) struct CleartextMessage {
+    // lines of the text without dash-escaping
+    lines: Vec<String>,
+    signatures: Vec<StandaloneSignature>,
+}
+
+impl CleartextMessage {
+    /// Parses a clearsigned message, which has to start with its header.
+    #[allow(dead_code)]
+    pub(crate) fn parse(message: &str) -> Result<CleartextMessage, String> {
+        let mut lines = message.lines();
+        if lines.next().map(|line| line.trim_end()) != Some(MESSAGE_HEADER) {
+            return Err(format!("No \"{}\"", MESSAGE_HEADER));
+        }
+
+        // only Hash headers are allowed, anything else could hide text that isn't shown
+        for line in lines.by_ref() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if !line.starts_with("Hash:") {
+                return Err(format!("Unexpected armor header \"{}\"", line));
+            }
+        }
+
+        let mut text = Vec::new();
+        let mut signature = None;
+        for line in lines.by_ref() {
+            if line.trim_end() == SIGNATURE_HEADER {
+                signature = Some(vec![line]);
+                break;
+            }
+            match line.strip_prefix("- ") {
+                Some(unescaped) => text.push(unescaped.to_string()),
+                None if line.starts_with('-') => {
+                    return Err(format!("Line \"{}\" isn't dash-escaped", line))
+                }
+                None => text.push(line.to_string()),
+            }
+        }
+        let mut signature = signature.ok_or_else(|| format!("No \"{}\"", SIGNATURE_HEADER))?;
+        signature.extend(lines);
+
+        let (signatures, _) =
+            StandaloneSignature::from_armor_many(Cursor::new(signature.join("\n").into_bytes()))
+                .map_err(|e| e.to_string())?;
+        let signatures = signatures
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if signatures.is_empty() {
+            return Err("No signature".to_string());
+        }
+
+        Ok(CleartextMessage {
+            lines: text,
+            signatures,
+        })
+    }
+
+    /// Signs a text with the primary key of a secret key, returns the armored message.
+    #[allow(dead_code)]
+    pub(crate) fn sign<F>(
+        text: &str,
+        key: &SignedSecretKey,
+        key_pw: F,
+    ) -> pgp::errors::Result<String>
+    where
+        F: FnOnce() -> String,
+    {
+        let message = CleartextMessage {
+            lines: text.lines().map(|line| line.to_string()).collect(),
+            signatures: Vec::new(),
+        };
+        let config = SignatureConfig::new_v4(
+            Default::default(),
+            SignatureType::Text,
+            key.algorithm(),
+            HashAlgorithm::SHA2_256,
+            vec![Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc::now().trunc_subsecs(0),
+            ))],
+            vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))],
+        );
+        let signature = config.sign(key, key_pw, &message.signed_data()[..])?;
+
+        let escaped: Vec<String> = message
+            .lines
+            .iter()
+            .map(|line| {
+                if line.starts_with('-') {
+                    format!("- {}", line)
+                } else {
+                    line.clone()
+                }
+            })
+            .collect();
+        Ok(format!(
+            "{}\nHash: SHA256\n\n{}\n{}",
+            MESSAGE_HEADER,
+            escaped.join("\n"),
+            StandaloneSignature::new(signature).to_armored_string(None)?
+        ))
+    }
+
+    /// Text of the message, lines separated by "\n".
+    #[allow(dead_code)]
+    pub(crate) fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Whether any of the signatures of the message was made by a key.
+    #[allow(dead_code)]
+    pub(crate) fn verify(&self, key: &impl PublicKeyTrait) -> bool {
+        let data = self.signed_data();
+        self.signatures
+            .iter()
+            .any(|signature| signature.verify(key, &data).is_ok())
+    }
+
+    // lines without trailing whitespace and separated by CRLF, without the last line ending
+    fn signed_data(&self) -> Vec<u8> {
+        self.lines
+            .iter()
+            .map(|line| line.trim_end_matches([' ', '\t']))
+            .collect::<Vec<_>>()
+            .join("\r\n")
+            .into_bytes()
+    }
+}
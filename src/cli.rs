@@ -1,29 +1,42 @@
+mod cleartext;
 mod helper;
 mod models;
 
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
 use std::{error::Error, path::PathBuf};
 
 use bls12_381_plus::{G1Projective, Scalar};
 use clap::{value_parser, Arg, ArgAction, Command};
+use ed25519_dalek::SigningKey;
+use helper::audit::{parse_public_key, verify_audit_log};
+use helper::hash::{salted_sha256_hex, sha256_hex};
 use helper::strings::null_terminated_bytes_to_string;
 use helper::vsss::base64_str_to_share;
+use pgp::composed::{Deserializable, SignedSecretKey};
 use rand::rngs::OsRng;
 use vsss_rs::{Feldman, Share};
 
-use crate::models::layer::{Layer, LayerCommands, LayerState, VSSSMetadata};
+use crate::cleartext::CleartextMessage;
+use crate::models::audit::AuditExport;
+use crate::models::dms::Challenge;
+use crate::models::layer::{
+    Layer, LayerAccess, LayerCommands, LayerState, SecretVerifier, VSSSMetadata,
+};
+
+// shares and the verifier of a split secret
+type SplitSecret = (
+    Vec<vsss_rs::Share>,
+    vsss_rs::FeldmanVerifier<Scalar, G1Projective>,
+);
 
 fn split_secret(
     secret_str: &str,
     threshold: &u8,
     shares: &u8,
-) -> Result<
-    (
-        Vec<vsss_rs::Share>,
-        vsss_rs::FeldmanVerifier<Scalar, G1Projective>,
-    ),
-    Box<dyn Error>,
-> {
-    if secret_str.len() < 1 || secret_str.len() > 32 {
+) -> Result<SplitSecret, Box<dyn Error>> {
+    if secret_str.is_empty() || secret_str.len() > 32 {
         return Err("Secret must be 1-32 bytes in size".into());
     }
     let mut input_bytes = [0u8; 32];
@@ -37,7 +50,7 @@ fn split_secret(
         t: *threshold as usize,
         n: *shares as usize,
     }
-    .split_secret::<Scalar, G1Projective, OsRng>(secret, None, &mut OsRng::default());
+    .split_secret::<Scalar, G1Projective, OsRng>(secret, None, &mut OsRng);
     match res {
         Ok((shares, verifier)) => Ok((shares, verifier)),
         Err(e) => Err(format!("Failed to split secret ({:?})", e).into()),
@@ -78,6 +91,143 @@ fn read_and_verify_shares(vsss: &VSSSMetadata) -> Result<Vec<Share>, Box<dyn Err
     Ok(shares)
 }
 
+fn export_audit_log(
+    server_url: &str,
+    admin_token: &str,
+) -> Result<(AuditExport, String), Box<dyn Error>> {
+    let response = reqwest::blocking::Client::new()
+        .get(format!("{}/audit", server_url.trim_end_matches('/')))
+        .bearer_auth(admin_token)
+        .send()?
+        .error_for_status()?;
+    let body = response.text()?;
+    Ok((serde_json::from_str(&body)?, body))
+}
+
+/// Fetches the challenge of a perimetr-dms and signs the response with an OpenPGP secret key.
+fn respond_to_challenge(
+    challenge_url: &str,
+    secret_key: &PathBuf,
+    passphrase: Option<&String>,
+) -> Result<(Challenge, String), Box<dyn Error>> {
+    let text = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?
+        .get(challenge_url)
+        .send()?
+        .error_for_status()?
+        .text()?;
+    let challenge = Challenge::parse(&text)?;
+
+    let (key, _) = SignedSecretKey::from_armor_single(std::fs::File::open(secret_key)?)?;
+    key.verify()?;
+    let passphrase = match passphrase {
+        Some(passphrase) => passphrase.clone(),
+        None if key.primary_key.secret_params().is_encrypted() => {
+            rpassword::prompt_password("Passphrase of the secret key: ")?
+        }
+        None => String::new(),
+    };
+
+    let response = CleartextMessage::sign(&challenge.response(), &key, || passphrase)?;
+    Ok((challenge, response))
+}
+
+/// Sends a request to the admin API over its Unix socket and returns status and body.
+fn admin_request(
+    socket: &PathBuf,
+    admin_token: Option<&String>,
+    method: &str,
+    path: &str,
+) -> Result<(u16, String), Box<dyn Error>> {
+    let mut stream = UnixStream::connect(socket)?;
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n",
+        method, path
+    );
+    if let Some(admin_token) = admin_token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", admin_token));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or("Malformed HTTP response")?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or("Malformed HTTP status line")?;
+
+    let chunked = head.lines().any(|line| {
+        line.to_ascii_lowercase()
+            .starts_with("transfer-encoding: chunked")
+    });
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_string()
+    };
+
+    Ok((status, body))
+}
+
+fn decode_chunked(mut body: &str) -> Result<String, Box<dyn Error>> {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n").ok_or("Malformed chunk")?;
+        let size = usize::from_str_radix(size.trim(), 16)?;
+        if size == 0 {
+            return Ok(decoded);
+        }
+        decoded.push_str(rest.get(..size).ok_or("Truncated chunk")?);
+        body = rest.get(size + 2..).ok_or("Truncated chunk")?;
+    }
+}
+
+fn read_audit_export(input: &PathBuf) -> Result<AuditExport, Box<dyn Error>> {
+    let reader = std::fs::File::open(input)?;
+    Ok(serde_json::from_reader(reader)?)
+}
+
+/// Prints the result of the verification and exits with 1 if it failed.
+fn verify_audit_export(export: &AuditExport, pinned_public_key: Option<&String>) {
+    let public_key = match pinned_public_key.or(export.public_key.as_ref()) {
+        Some(public_key) => match parse_public_key(public_key) {
+            Ok(public_key) => Some(public_key),
+            Err(e) => {
+                println!("Error: Invalid public key ({})", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if let Err(e) = verify_audit_log(&export.entries, public_key.as_ref()) {
+        println!("Error: Audit log verification failed ({})", e);
+        std::process::exit(1);
+    }
+
+    match export.entries.last() {
+        Some(last) => println!(
+            "Audit log of {} entries is intact, latest hash: {}",
+            export.entries.len(),
+            last.hash
+        ),
+        None => println!("Audit log is empty"),
+    }
+    if public_key.is_none() {
+        println!("Warning: Entries are not signed, only the hash chain was verified");
+    } else if pinned_public_key.is_none() {
+        println!("Warning: Signatures were checked against the public key in the export, pin the key of the server with --public-key");
+    }
+}
+
 fn main() {
     let matches = Command::new("perimetr")
         .about("CLI tool to generate perimetr layers and decrypt them manually if needed.")
@@ -129,6 +279,174 @@ fn main() {
                         .value_parser(value_parser!(PathBuf)),
                 ),
         )
+        .subcommand(
+            Command::new("token")
+                .about("Generate a download token for a beneficiary and the hash to store in the layer metadata"),
+        )
+        .subcommand(
+            Command::new("respond")
+                .about("Answer the challenge of a perimetr-dms with a signed response")
+                .arg(
+                    Arg::new("challenge-url")
+                        .short('u')
+                        .long("challenge-url")
+                        .help("URL of the published challenge, f.e. https://example.net/dms-challenge")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("secret-key")
+                        .short('k')
+                        .long("secret-key")
+                        .help("Armored OpenPGP secret key to sign with, f.e. from gpg --export-secret-keys --armor")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("passphrase")
+                        .long("passphrase")
+                        .env("PERIMETR_KEY_PASSPHRASE")
+                        .help("Passphrase of the secret key, asked for if the key is protected")
+                        .required(false)
+                        .hide_env_values(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Path to write the signed response to instead of stdout")
+                        .required(false)
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .about("Export and verify the audit log of a perimetr-server")
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("export")
+                        .about("Download the audit log and verify it")
+                        .arg(
+                            Arg::new("server-url")
+                                .short('u')
+                                .long("server-url")
+                                .help("URL of the perimetr-server, f.e. https://perimetr.example.net, or of an estate, f.e. https://perimetr.example.net/estates/doe")
+                                .required(true)
+                                .value_parser(value_parser!(String)),
+                        )
+                        .arg(
+                            Arg::new("admin-token")
+                                .long("admin-token")
+                                .env("PERIMETR_ADMIN_TOKEN")
+                                .help("Admin token of the perimetr-server")
+                                .required(true)
+                                .hide_env_values(true)
+                                .value_parser(value_parser!(String)),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .help("Path to store the audit log as JSON")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Verify the hash chain and signatures of an exported audit log")
+                        .arg(
+                            Arg::new("input")
+                                .short('i')
+                                .long("input")
+                                .help("Path to an exported audit log")
+                                .required(true)
+                                .value_parser(value_parser!(PathBuf)),
+                        )
+                        .arg(
+                            Arg::new("public-key")
+                                .short('k')
+                                .long("public-key")
+                                .help("Public key of the server to check signatures against, instead of the one in the export")
+                                .required(false)
+                                .value_parser(value_parser!(String)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("keygen")
+                        .about("Generate a key pair to sign audit log entries"),
+                ),
+        )
+        .subcommand(
+            Command::new("admin")
+                .about("Administrate layers through the admin API of a perimetr-server")
+                .arg_required_else_help(true)
+                .arg(
+                    Arg::new("socket")
+                        .short('S')
+                        .long("socket")
+                        .env("PERIMETR_ADMIN_SOCKET")
+                        .help("Unix socket of the admin API")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("admin-token")
+                        .long("admin-token")
+                        .env("PERIMETR_ADMIN_TOKEN")
+                        .help("Admin token of the perimetr-server, if configured")
+                        .required(false)
+                        .hide_env_values(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("estate")
+                        .short('e')
+                        .long("estate")
+                        .env("PERIMETR_ESTATE")
+                        .help("Id of the estate, if the server hosts several")
+                        .required(false)
+                        .value_parser(value_parser!(String)),
+                )
+                .subcommand(Command::new("layers").about("List all layers with their status"))
+                .subcommand(
+                    Command::new("reset")
+                        .about("Reset the state of a layer to idle")
+                        .arg(Arg::new("uuid").help("Layer UUID").required(true)),
+                )
+                .subcommand(
+                    Command::new("retire")
+                        .about("Retire a layer, it refuses shares until it is reset")
+                        .arg(Arg::new("uuid").help("Layer UUID").required(true)),
+                )
+                .subcommand(
+                    Command::new("decrypt")
+                        .about("Run the decryption of a layer again")
+                        .arg(Arg::new("uuid").help("Layer UUID").required(true)),
+                )
+                .subcommand(
+                    Command::new("shares")
+                        .about("List the shares stored for a layer")
+                        .arg(Arg::new("uuid").help("Layer UUID").required(true)),
+                )
+                .subcommand(
+                    Command::new("purge")
+                        .about("Delete one or all shares of a layer")
+                        .arg(Arg::new("uuid").help("Layer UUID").required(true))
+                        .arg(
+                            Arg::new("id")
+                                .long("id")
+                                .help("ID of a single share to delete, see the shares command")
+                                .required(false)
+                                .value_parser(value_parser!(i32)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("rescan")
+                        .about("Re-scan the layer path and report unreadable layer files"),
+                ),
+        )
         .get_matches();
 
     match matches.subcommand() {
@@ -148,6 +466,12 @@ fn main() {
                 state: LayerState::Idle,
                 commands: Vec::new(),
                 vsss: None,
+                secret_verifier: None,
+                unlocks: Vec::new(),
+                access: LayerAccess::default(),
+                beneficiaries: Vec::new(),
+                sandbox: None,
+                output_dir: None,
             };
 
             let metadata_path = if metadata_path.is_dir() {
@@ -188,41 +512,37 @@ fn main() {
                 println!();
             } else {
                 println!("Threshold is 1, no need to split secret.");
+                println!("Please provide the secret on STDIN to let the server verify it, or leave empty to skip.");
+
+                let input = rpassword::read_password();
+                if let Err(e) = input {
+                    println!("Error: Failed to read input ({})", e);
+                    std::process::exit(1);
+                }
+                let input = input.unwrap();
+                let input = input.trim();
+
+                if !input.is_empty() {
+                    let salt = rand::random::<[u8; 16]>();
+                    layer.secret_verifier = Some(SecretVerifier {
+                        salt: base64::encode(salt),
+                        sha256: salted_sha256_hex(&salt, input.as_bytes()),
+                    });
+                }
             }
 
             if *split_matches.get_one("default-actions").unwrap_or(&false) {
                 layer.commands = vec![
-                    LayerCommands {
-                        program: "gpg".to_string(),
-                        args: vec![
-                            "--decrypt",
-                            "--passphrase-fd",
-                            "0",
-                            "--batch",
-                            "-o",
-                            &format!("{}.tar.zst", layer.uuid).to_string(),
-                            &format!("{}.tar.zst.gpg", layer.uuid).to_string(),
-                        ]
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect(),
-                        working_dir: ".".to_string(),
-                        secret_stdin: true,
+                    LayerCommands::DecryptOpenpgp {
+                        input: "{uuid}.tar.zst.gpg".to_string(),
+                        output: "{scratch_dir}/{uuid}.tar.zst".to_string(),
                     },
-                    LayerCommands {
-                        program: "tar".to_string(),
-                        args: vec!["xf".to_string(), format!("{}.tar.zst", layer.uuid)],
-                        working_dir: ".".to_string(),
-                        secret_stdin: false,
+                    LayerCommands::Extract {
+                        input: "{scratch_dir}/{uuid}.tar.zst".to_string(),
+                        destination: "{output_dir}".to_string(),
                     },
-                    LayerCommands {
-                        program: "rm".to_string(),
-                        args: vec![
-                            format!("{}.tar.zst", layer.uuid),
-                            format!("{}.tar.zst.gpg", layer.uuid),
-                        ],
-                        working_dir: ".".to_string(),
-                        secret_stdin: false,
+                    LayerCommands::SecureDelete {
+                        paths: vec!["{uuid}.tar.zst.gpg".to_string()],
                     },
                 ];
             }
@@ -233,6 +553,26 @@ fn main() {
             }
 
             println!("Metadata written to \"{}\".", metadata_path.display());
+
+            // expanded like the server does, the scratch directory only exists during a decryption
+            let layer_dir = metadata_path
+                .parent()
+                .unwrap_or(".".as_ref())
+                .canonicalize()
+                .unwrap_or_default();
+            let placeholders = layer.placeholders(&layer_dir, &layer_dir.join(".scratch"));
+            for command in layer.commands.iter() {
+                if let Ok(
+                    LayerCommands::DecryptOpenpgp { input, .. }
+                    | LayerCommands::DecryptAge { input, .. },
+                ) = command.expand(&placeholders)
+                {
+                    println!(
+                        "Place the encrypted payload at \"{}\".",
+                        layer_dir.join(input).display()
+                    );
+                }
+            }
         }
         Some(("combine", _combine_matches)) => {
             let metadata_file: &PathBuf = matches.get_one("metadata-file").unwrap();
@@ -268,6 +608,157 @@ fn main() {
 
             println!("Secret: {}", res.unwrap());
         }
+        Some(("token", _token_matches)) => {
+            let token = base64::encode_config(rand::random::<[u8; 24]>(), base64::URL_SAFE_NO_PAD);
+
+            println!("Token for the beneficiary: {}", token);
+            println!(
+                "SHA-256 for the layer metadata: {}",
+                sha256_hex(token.as_bytes())
+            );
+        }
+        Some(("respond", respond_matches)) => {
+            // safe unwraps because of required(true)
+            let challenge_url: &String = respond_matches.get_one("challenge-url").unwrap();
+            let secret_key: &PathBuf = respond_matches.get_one("secret-key").unwrap();
+            let passphrase: Option<&String> = respond_matches.get_one("passphrase");
+            let output: Option<&PathBuf> = respond_matches.get_one("output");
+
+            let res = respond_to_challenge(challenge_url, secret_key, passphrase);
+            if let Err(e) = res {
+                println!("Error: Failed to answer challenge ({})", e);
+                std::process::exit(1);
+            }
+            let (challenge, response) = res.unwrap();
+
+            match output {
+                Some(output) => {
+                    if let Err(e) = std::fs::write(output, response) {
+                        println!("Error: Failed to write {} ({})", output.display(), e);
+                        std::process::exit(1);
+                    }
+                    println!(
+                        "Signed response to the challenge issued {} written to {}",
+                        challenge.issued,
+                        output.display()
+                    );
+                }
+                // only the response, so it can be piped to where it is published
+                None => print!("{}", response),
+            }
+        }
+        Some(("audit", audit_matches)) => match audit_matches.subcommand() {
+            Some(("export", export_matches)) => {
+                // safe unwraps because of required(true)
+                let server_url: &String = export_matches.get_one("server-url").unwrap();
+                let admin_token: &String = export_matches.get_one("admin-token").unwrap();
+                let output: &PathBuf = export_matches.get_one("output").unwrap();
+
+                let res = export_audit_log(server_url, admin_token);
+                if let Err(e) = res {
+                    println!("Error: Failed to export audit log ({})", e);
+                    std::process::exit(1);
+                }
+                let (export, body) = res.unwrap();
+
+                if let Err(e) = std::fs::write(output, body) {
+                    println!("Error: Failed to write {} ({})", output.display(), e);
+                    std::process::exit(1);
+                }
+                println!(
+                    "Exported {} entries to {}",
+                    export.entries.len(),
+                    output.display()
+                );
+
+                verify_audit_export(&export, None);
+            }
+            Some(("verify", verify_matches)) => {
+                let input: &PathBuf = verify_matches.get_one("input").unwrap();
+                let public_key: Option<&String> = verify_matches.get_one("public-key");
+
+                let res = read_audit_export(input);
+                if let Err(e) = res {
+                    println!("Error: Failed to read {} ({})", input.display(), e);
+                    std::process::exit(1);
+                }
+
+                verify_audit_export(&res.unwrap(), public_key);
+            }
+            Some(("keygen", _keygen_matches)) => {
+                let signing_key = SigningKey::from_bytes(&rand::random::<[u8; 32]>());
+
+                println!(
+                    "Signing key for the server configuration: {}",
+                    base64::encode(signing_key.to_bytes())
+                );
+                println!(
+                    "Public key to verify the audit log: {}",
+                    base64::encode(signing_key.verifying_key().to_bytes())
+                );
+            }
+            _ => unreachable!(),
+        },
+        Some(("admin", admin_matches)) => {
+            // safe unwraps because of required(true)
+            let socket: &PathBuf = admin_matches.get_one("socket").unwrap();
+            let admin_token: Option<&String> = admin_matches.get_one("admin-token");
+
+            let (method, path) = match admin_matches.subcommand() {
+                Some(("layers", _)) => ("GET", "/layers".to_string()),
+                Some(("reset", m)) => (
+                    "POST",
+                    format!("/layer/{}/reset", m.get_one::<String>("uuid").unwrap()),
+                ),
+                Some(("retire", m)) => (
+                    "POST",
+                    format!("/layer/{}/retire", m.get_one::<String>("uuid").unwrap()),
+                ),
+                Some(("decrypt", m)) => (
+                    "POST",
+                    format!("/layer/{}/decrypt", m.get_one::<String>("uuid").unwrap()),
+                ),
+                Some(("shares", m)) => (
+                    "GET",
+                    format!("/layer/{}/shares", m.get_one::<String>("uuid").unwrap()),
+                ),
+                Some(("purge", m)) => {
+                    let uuid = m.get_one::<String>("uuid").unwrap();
+                    match m.get_one::<i32>("id") {
+                        Some(id) => ("DELETE", format!("/layer/{}/shares?id={}", uuid, id)),
+                        None => ("DELETE", format!("/layer/{}/shares", uuid)),
+                    }
+                }
+                Some(("rescan", _)) => ("POST", "/rescan".to_string()),
+                _ => unreachable!(),
+            };
+
+            let path = match admin_matches.get_one::<String>("estate") {
+                Some(estate) => format!("/estates/{}{}", estate, path),
+                None => path,
+            };
+
+            let res = admin_request(socket, admin_token, method, &path);
+            if let Err(e) = res {
+                println!(
+                    "Error: Failed to reach admin API on {} ({})",
+                    socket.display(),
+                    e
+                );
+                std::process::exit(1);
+            }
+            let (status, body) = res.unwrap();
+
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap_or(body.into());
+            if !(200..300).contains(&status) {
+                match body.get("message").and_then(|message| message.as_str()) {
+                    Some(message) => println!("Error: {}", message),
+                    None => println!("Error: Admin API returned status {}", status),
+                }
+                std::process::exit(1);
+            }
+            println!("{}", serde_json::to_string_pretty(&body).unwrap());
+        }
         _ => unreachable!(),
     }
 }
@@ -1 +1,157 @@
-pub(crate) mod shares;
+pub(crate) mod postgres;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
+
+use std::error::Error as StdError;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ed25519_dalek::SigningKey;
+use sqlx::migrate::MigrateError;
+use sqlx::Error;
+
+use crate::helper::output::CapturedOutput;
+use crate::models::admin::ShareRecord;
+use crate::models::audit::AuditEntry;
+use crate::models::delivery::{Delivery, DeliveryState};
+use crate::models::job::{Job, JobState, JobSummary};
+
+pub(crate) struct JobStats {
+    pub(crate) estate: String,
+    pub(crate) state: String,
+    pub(crate) count: i64,
+    // seconds of finished jobs
+    pub(crate) duration_sum: f64,
+}
+
+pub(crate) struct PoolStats {
+    pub(crate) size: u32,
+    pub(crate) idle: u32,
+    pub(crate) max_connections: u32,
+}
+
+/// Persistence of shares, jobs, deliveries and the audit log, implemented per database backend.
+#[async_trait]
+pub(crate) trait Storage: Send + Sync {
+    async fn migrate(&self) -> Result<(), MigrateError>;
+    async fn ping(&self) -> Result<(), Error>;
+    fn pool_stats(&self) -> PoolStats;
+
+    /// Stores a share and returns false if the layer already has it.
+    async fn insert_share(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+        share: String,
+    ) -> Result<bool, Error>;
+    async fn count_shares(&self, estate: &str, layer_uuid: String) -> Result<Option<i64>, Error>;
+    async fn count_shares_by_layer(&self) -> Result<Vec<(String, String, i64)>, Error>;
+    async fn select_shares(&self, estate: &str, layer_uuid: String) -> Result<Vec<String>, Error>;
+    async fn select_share_records(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+    ) -> Result<Vec<ShareRecord>, Error>;
+    /// Deletes a single share or, without an id, all shares of the layer.
+    async fn delete_shares(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+        share_id: Option<i32>,
+    ) -> Result<u64, Error>;
+
+    async fn insert_job(&self, estate: &str, layer_uuid: String) -> Result<i32, Error>;
+    async fn finish_job(
+        &self,
+        job_id: i32,
+        state: JobState,
+        error: Option<String>,
+    ) -> Result<bool, Error>;
+    async fn insert_job_command(
+        &self,
+        job_id: i32,
+        program: String,
+        exit_status: Option<i32>,
+        stdout: CapturedOutput,
+        stderr: CapturedOutput,
+    ) -> Result<bool, Error>;
+    async fn select_jobs(&self, estate: &str, layer_uuid: String) -> Result<Vec<Job>, Error>;
+    async fn select_latest_job(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+    ) -> Result<Option<JobSummary>, Error>;
+    async fn select_job_stats(&self) -> Result<Vec<JobStats>, Error>;
+
+    async fn insert_delivery(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+        beneficiary: String,
+        method: String,
+    ) -> Result<i32, Error>;
+    async fn finish_delivery(
+        &self,
+        delivery_id: i32,
+        state: DeliveryState,
+        error: Option<String>,
+    ) -> Result<bool, Error>;
+    async fn select_deliveries(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+    ) -> Result<Vec<Delivery>, Error>;
+
+    /// Appends an entry to the audit log, chained to the latest entry of the estate and signed if a key is given.
+    async fn insert_audit_entry(
+        &self,
+        estate: &str,
+        signing_key: Option<&SigningKey>,
+        event: &str,
+        layer_uuid: Option<String>,
+        actor: &str,
+        details: String,
+    ) -> Result<i64, Error>;
+    async fn select_audit_entries(&self, estate: &str) -> Result<Vec<AuditEntry>, Error>;
+    // counts by estate and event
+    async fn count_audit_events(
+        &self,
+        events: &[&str],
+    ) -> Result<Vec<(String, String, i64)>, Error>;
+}
+
+/// Database backend, selected by the scheme of the database URL.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Backend {
+    Postgres,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl Backend {
+    pub(crate) fn from_url(url: &str) -> Result<Backend, String> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Ok(Backend::Postgres);
+        }
+        if url.starts_with("sqlite:") {
+            #[cfg(feature = "sqlite")]
+            return Ok(Backend::Sqlite);
+            #[cfg(not(feature = "sqlite"))]
+            return Err(
+                "Database URL is an SQLite URL, but perimetr-server was built without the sqlite feature"
+                    .to_string(),
+            );
+        }
+        Err("Database URL must be a postgres:// or sqlite: URL".to_string())
+    }
+}
+
+/// Connects to the database backend selected by the URL.
+pub(crate) async fn connect(url: &str) -> Result<Arc<dyn Storage>, Box<dyn StdError>> {
+    let storage: Arc<dyn Storage> = match Backend::from_url(url)? {
+        Backend::Postgres => Arc::new(postgres::PostgresStorage::connect(url).await?),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => Arc::new(sqlite::SqliteStorage::connect(url).await?),
+    };
+    Ok(storage)
+}
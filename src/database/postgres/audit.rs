@@ -0,0 +1,75 @@
+use chrono::{SubsecRound, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use sqlx::{Error, Pool, Postgres};
+
+use crate::helper::audit::{audit_entry_hash, GENESIS_HASH};
+use crate::models::audit::AuditEntry;
+
+/// Appends an entry to the audit log, chained to the latest entry of the estate and signed if a key is given.
+pub(crate) async fn insert_audit_entry(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    signing_key: Option<&SigningKey>,
+    event: &str,
+    layer_uuid: Option<String>,
+    actor: &str,
+    details: String,
+) -> Result<i64, Error> {
+    let mut tx = db_pool.begin().await?;
+
+    // serializes appends, so every entry is chained to its predecessor
+    sqlx::query!("LOCK TABLE audit_log IN EXCLUSIVE MODE")
+        .execute(&mut tx)
+        .await?;
+
+    let prev_hash = sqlx::query!(
+        "SELECT hash FROM audit_log WHERE estate = $1 ORDER BY id DESC LIMIT 1",
+        estate,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    .map(|row| row.hash)
+    .unwrap_or_else(|| GENESIS_HASH.to_string());
+
+    let created_at = Utc::now().trunc_subsecs(6);
+    let hash = audit_entry_hash(&prev_hash, &created_at, event, &layer_uuid, actor, &details);
+    let signature = signing_key.map(|key| base64::encode(key.sign(hash.as_bytes()).to_bytes()));
+
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO audit_log (estate, created_at, event, layer_uuid, actor, details, prev_hash, hash, signature)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id
+        "#,
+        estate,
+        created_at,
+        event,
+        layer_uuid,
+        actor,
+        details,
+        prev_hash,
+        hash,
+        signature,
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(result.id)
+}
+
+pub(crate) async fn select_audit_entries(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+) -> Result<Vec<AuditEntry>, Error> {
+    sqlx::query_as!(
+        AuditEntry,
+        r#"
+            SELECT id, created_at, event, layer_uuid, actor, details, prev_hash, hash, signature
+            FROM audit_log WHERE estate = $1 ORDER BY id
+        "#,
+        estate,
+    )
+    .fetch_all(db_pool)
+    .await
+}
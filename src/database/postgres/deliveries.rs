@@ -0,0 +1,79 @@
+use sqlx::{Error, Pool, Postgres};
+
+use crate::models::delivery::{Delivery, DeliveryState};
+
+pub(crate) async fn insert_delivery(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+    beneficiary: String,
+    method: String,
+) -> Result<i32, Error> {
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO deliveries (estate, layer_uuid, beneficiary, method, state)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+        "#,
+        estate,
+        layer_uuid,
+        beneficiary,
+        method,
+        DeliveryState::Pending.as_str(),
+    )
+    .fetch_one(db_pool)
+    .await?;
+    Ok(result.id)
+}
+
+pub(crate) async fn finish_delivery(
+    db_pool: &Pool<Postgres>,
+    delivery_id: i32,
+    state: DeliveryState,
+    error: Option<String>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+            UPDATE deliveries SET state = $2, error = $3, finished_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+        "#,
+        delivery_id,
+        state.as_str(),
+        error,
+    )
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn select_deliveries(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<Delivery>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT id, layer_uuid, beneficiary, method, state, error, created_at, finished_at
+            FROM deliveries
+            WHERE estate = $1 AND layer_uuid = $2
+            ORDER BY created_at DESC, id DESC
+        "#,
+        estate,
+        layer_uuid,
+    )
+    .fetch_all(db_pool)
+    .await?;
+    Ok(result
+        .into_iter()
+        .map(|r| Delivery {
+            id: r.id,
+            layer_uuid: r.layer_uuid,
+            beneficiary: r.beneficiary,
+            method: r.method,
+            state: DeliveryState::parse(&r.state).unwrap_or(DeliveryState::Failed),
+            error: r.error,
+            created_at: r.created_at,
+            finished_at: r.finished_at,
+        })
+        .collect())
+}
@@ -0,0 +1,150 @@
+use sqlx::{Error, Pool, Postgres};
+
+use crate::helper::output::CapturedOutput;
+use crate::models::job::{Job, JobCommand, JobState, JobSummary};
+
+pub(crate) async fn insert_job(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<i32, Error> {
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO jobs (estate, layer_uuid, state)
+            VALUES ($1, $2, $3)
+            RETURNING id
+        "#,
+        estate,
+        layer_uuid,
+        JobState::Running.as_str(),
+    )
+    .fetch_one(db_pool)
+    .await?;
+    Ok(result.id)
+}
+
+pub(crate) async fn finish_job(
+    db_pool: &Pool<Postgres>,
+    job_id: i32,
+    state: JobState,
+    error: Option<String>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+            UPDATE jobs SET state = $2, error = $3, finished_at = CURRENT_TIMESTAMP
+            WHERE id = $1
+        "#,
+        job_id,
+        state.as_str(),
+        error,
+    )
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn insert_job_command(
+    db_pool: &Pool<Postgres>,
+    job_id: i32,
+    program: String,
+    exit_status: Option<i32>,
+    stdout: CapturedOutput,
+    stderr: CapturedOutput,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO job_commands (job_id, program, exit_status, stdout, stdout_truncated, stderr, stderr_truncated)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        job_id,
+        program,
+        exit_status,
+        stdout.text,
+        stdout.truncated,
+        stderr.text,
+        stderr.truncated,
+    )
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn select_jobs(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<Job>, Error> {
+    let job_rows = sqlx::query!(
+        r#"
+            SELECT id, layer_uuid, state, error, started_at, finished_at FROM jobs
+            WHERE estate = $1 AND layer_uuid = $2
+            ORDER BY started_at DESC
+        "#,
+        estate,
+        layer_uuid,
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut jobs = Vec::with_capacity(job_rows.len());
+    for job_row in job_rows {
+        let command_rows = sqlx::query!(
+            r#"
+                SELECT program, exit_status, stdout, stdout_truncated, stderr, stderr_truncated, created_at
+                FROM job_commands
+                WHERE job_id = $1
+                ORDER BY id
+            "#,
+            job_row.id,
+        )
+        .fetch_all(db_pool)
+        .await?;
+
+        jobs.push(Job {
+            id: job_row.id,
+            layer_uuid: job_row.layer_uuid,
+            state: JobState::parse(&job_row.state).unwrap_or(JobState::Failed),
+            error: job_row.error,
+            started_at: job_row.started_at,
+            finished_at: job_row.finished_at,
+            commands: command_rows
+                .into_iter()
+                .map(|r| JobCommand {
+                    program: r.program,
+                    exit_status: r.exit_status,
+                    stdout: r.stdout,
+                    stdout_truncated: r.stdout_truncated,
+                    stderr: r.stderr,
+                    stderr_truncated: r.stderr_truncated,
+                    created_at: r.created_at,
+                })
+                .collect(),
+        });
+    }
+    Ok(jobs)
+}
+
+pub(crate) async fn select_latest_job(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Option<JobSummary>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT id, state, started_at, finished_at FROM jobs
+            WHERE estate = $1 AND layer_uuid = $2
+            ORDER BY started_at DESC
+            LIMIT 1
+        "#,
+        estate,
+        layer_uuid,
+    )
+    .fetch_optional(db_pool)
+    .await?;
+    Ok(result.map(|r| JobSummary {
+        id: r.id,
+        state: JobState::parse(&r.state).unwrap_or(JobState::Failed),
+        started_at: r.started_at,
+        finished_at: r.finished_at,
+    }))
+}
@@ -0,0 +1,71 @@
+use sqlx::{Error, Pool, Postgres};
+
+use crate::database::JobStats;
+
+pub(crate) async fn ping(db_pool: &Pool<Postgres>) -> Result<(), Error> {
+    sqlx::query!("SELECT 1 AS one").fetch_one(db_pool).await?;
+    Ok(())
+}
+
+pub(crate) async fn count_shares_by_layer(
+    db_pool: &Pool<Postgres>,
+) -> Result<Vec<(String, String, i64)>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT estate, layer_uuid, COUNT(share) AS "count!" FROM shares
+            GROUP BY estate, layer_uuid
+        "#
+    )
+    .fetch_all(db_pool)
+    .await?;
+    Ok(result
+        .into_iter()
+        .map(|r| (r.estate, r.layer_uuid, r.count))
+        .collect())
+}
+
+pub(crate) async fn select_job_stats(db_pool: &Pool<Postgres>) -> Result<Vec<JobStats>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT
+                estate,
+                state,
+                COUNT(id) AS "count!",
+                COALESCE(SUM(EXTRACT(EPOCH FROM (finished_at - started_at))), 0)::FLOAT8 AS "duration_sum!"
+            FROM jobs
+            GROUP BY estate, state
+        "#
+    )
+    .fetch_all(db_pool)
+    .await?;
+    Ok(result
+        .into_iter()
+        .map(|r| JobStats {
+            estate: r.estate,
+            state: r.state,
+            count: r.count,
+            duration_sum: r.duration_sum,
+        })
+        .collect())
+}
+
+pub(crate) async fn count_audit_events(
+    db_pool: &Pool<Postgres>,
+    events: &[&str],
+) -> Result<Vec<(String, String, i64)>, Error> {
+    let events: Vec<String> = events.iter().map(|event| event.to_string()).collect();
+    let result = sqlx::query!(
+        r#"
+            SELECT estate, event, COUNT(id) AS "count!" FROM audit_log
+            WHERE event = ANY($1)
+            GROUP BY estate, event
+        "#,
+        &events[..],
+    )
+    .fetch_all(db_pool)
+    .await?;
+    Ok(result
+        .into_iter()
+        .map(|r| (r.estate, r.event, r.count))
+        .collect())
+}
@@ -0,0 +1,107 @@
+use sqlx::{Error, Pool, Postgres};
+
+use crate::helper::hash::sha256_hex;
+use crate::models::admin::ShareRecord;
+
+pub(crate) async fn insert_share(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+    share: String,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+            INSERT INTO shares (estate, layer_uuid, share)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (estate, layer_uuid, share) DO NOTHING
+        "#,
+        estate,
+        layer_uuid,
+        share,
+    )
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn count_shares(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Option<i64>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT COUNT(share) FROM shares
+            WHERE estate = $1 AND layer_uuid = $2
+        "#,
+        estate,
+        layer_uuid,
+    )
+    .fetch_one(db_pool)
+    .await?;
+    Ok(result.count)
+}
+
+pub(crate) async fn select_shares(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<String>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT share FROM shares
+            WHERE estate = $1 AND layer_uuid = $2
+        "#,
+        estate,
+        layer_uuid,
+    )
+    .fetch_all(db_pool)
+    .await?;
+    Ok(result.into_iter().map(|r| r.share).collect())
+}
+
+pub(crate) async fn select_share_records(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<ShareRecord>, Error> {
+    let result = sqlx::query!(
+        r#"
+            SELECT id, share, created_at FROM shares
+            WHERE estate = $1 AND layer_uuid = $2
+            ORDER BY id
+        "#,
+        estate,
+        layer_uuid,
+    )
+    .fetch_all(db_pool)
+    .await?;
+    Ok(result
+        .into_iter()
+        .map(|r| ShareRecord {
+            id: r.id,
+            sha256: sha256_hex(r.share.as_bytes()),
+            created_at: r.created_at,
+        })
+        .collect())
+}
+
+pub(crate) async fn delete_shares(
+    db_pool: &Pool<Postgres>,
+    estate: &str,
+    layer_uuid: String,
+    share_id: Option<i32>,
+) -> Result<u64, Error> {
+    let result = sqlx::query!(
+        r#"
+            DELETE FROM shares
+            WHERE estate = $1 AND layer_uuid = $2 AND ($3::INTEGER IS NULL OR id = $3)
+        "#,
+        estate,
+        layer_uuid,
+        share_id,
+    )
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected())
+}
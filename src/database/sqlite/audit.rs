@@ -0,0 +1,85 @@
+use chrono::{SubsecRound, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use sqlx::{Error, Pool, Row, Sqlite};
+
+use crate::helper::audit::{audit_entry_hash, GENESIS_HASH};
+use crate::models::audit::AuditEntry;
+
+pub(crate) async fn insert_audit_entry(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    signing_key: Option<&SigningKey>,
+    event: &str,
+    layer_uuid: Option<String>,
+    actor: &str,
+    details: String,
+) -> Result<i64, Error> {
+    // appends are serialized by the single connection of the pool
+    let mut tx = db_pool.begin().await?;
+
+    let prev_hash =
+        sqlx::query("SELECT hash FROM audit_log WHERE estate = ?1 ORDER BY id DESC LIMIT 1")
+            .bind(estate)
+            .fetch_optional(&mut tx)
+            .await?
+            .map(|row| row.try_get("hash"))
+            .transpose()?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+
+    let created_at = Utc::now().trunc_subsecs(6);
+    let hash = audit_entry_hash(&prev_hash, &created_at, event, &layer_uuid, actor, &details);
+    let signature = signing_key.map(|key| base64::encode(key.sign(hash.as_bytes()).to_bytes()));
+
+    let result = sqlx::query(
+        r#"
+            INSERT INTO audit_log (estate, created_at, event, layer_uuid, actor, details, prev_hash, hash, signature)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            RETURNING id
+        "#,
+    )
+    .bind(estate)
+    .bind(created_at)
+    .bind(event)
+    .bind(layer_uuid)
+    .bind(actor)
+    .bind(details)
+    .bind(prev_hash)
+    .bind(hash)
+    .bind(signature)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    result.try_get("id")
+}
+
+pub(crate) async fn select_audit_entries(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+) -> Result<Vec<AuditEntry>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT id, created_at, event, layer_uuid, actor, details, prev_hash, hash, signature
+            FROM audit_log WHERE estate = ?1 ORDER BY id
+        "#,
+    )
+    .bind(estate)
+    .fetch_all(db_pool)
+    .await?;
+    result
+        .iter()
+        .map(|r| {
+            Ok(AuditEntry {
+                id: r.try_get("id")?,
+                created_at: r.try_get("created_at")?,
+                event: r.try_get("event")?,
+                layer_uuid: r.try_get("layer_uuid")?,
+                actor: r.try_get("actor")?,
+                details: r.try_get("details")?,
+                prev_hash: r.try_get("prev_hash")?,
+                hash: r.try_get("hash")?,
+                signature: r.try_get("signature")?,
+            })
+        })
+        .collect()
+}
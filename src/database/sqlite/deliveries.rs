@@ -0,0 +1,84 @@
+use chrono::Utc;
+use sqlx::{Error, Pool, Row, Sqlite};
+
+use crate::models::delivery::{Delivery, DeliveryState};
+
+pub(crate) async fn insert_delivery(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+    beneficiary: String,
+    method: String,
+) -> Result<i32, Error> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO deliveries (estate, layer_uuid, beneficiary, method, state, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            RETURNING id
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .bind(beneficiary)
+    .bind(method)
+    .bind(DeliveryState::Pending.as_str())
+    .bind(Utc::now())
+    .fetch_one(db_pool)
+    .await?;
+    result.try_get("id")
+}
+
+pub(crate) async fn finish_delivery(
+    db_pool: &Pool<Sqlite>,
+    delivery_id: i32,
+    state: DeliveryState,
+    error: Option<String>,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+            UPDATE deliveries SET state = ?2, error = ?3, finished_at = ?4
+            WHERE id = ?1
+        "#,
+    )
+    .bind(delivery_id)
+    .bind(state.as_str())
+    .bind(error)
+    .bind(Utc::now())
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn select_deliveries(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<Delivery>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT id, layer_uuid, beneficiary, method, state, error, created_at, finished_at
+            FROM deliveries
+            WHERE estate = ?1 AND layer_uuid = ?2
+            ORDER BY created_at DESC, id DESC
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .fetch_all(db_pool)
+    .await?;
+    result
+        .iter()
+        .map(|r| {
+            Ok(Delivery {
+                id: r.try_get("id")?,
+                layer_uuid: r.try_get("layer_uuid")?,
+                beneficiary: r.try_get("beneficiary")?,
+                method: r.try_get("method")?,
+                state: DeliveryState::parse(r.try_get("state")?).unwrap_or(DeliveryState::Failed),
+                error: r.try_get("error")?,
+                created_at: r.try_get("created_at")?,
+                finished_at: r.try_get("finished_at")?,
+            })
+        })
+        .collect()
+}
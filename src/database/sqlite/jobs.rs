@@ -0,0 +1,161 @@
+use chrono::Utc;
+use sqlx::{Error, Pool, Row, Sqlite};
+
+use crate::helper::output::CapturedOutput;
+use crate::models::job::{Job, JobCommand, JobState, JobSummary};
+
+pub(crate) async fn insert_job(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<i32, Error> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO jobs (estate, layer_uuid, state, started_at)
+            VALUES (?1, ?2, ?3, ?4)
+            RETURNING id
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .bind(JobState::Running.as_str())
+    .bind(Utc::now())
+    .fetch_one(db_pool)
+    .await?;
+    result.try_get("id")
+}
+
+pub(crate) async fn finish_job(
+    db_pool: &Pool<Sqlite>,
+    job_id: i32,
+    state: JobState,
+    error: Option<String>,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+            UPDATE jobs SET state = ?2, error = ?3, finished_at = ?4
+            WHERE id = ?1
+        "#,
+    )
+    .bind(job_id)
+    .bind(state.as_str())
+    .bind(error)
+    .bind(Utc::now())
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn insert_job_command(
+    db_pool: &Pool<Sqlite>,
+    job_id: i32,
+    program: String,
+    exit_status: Option<i32>,
+    stdout: CapturedOutput,
+    stderr: CapturedOutput,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO job_commands (job_id, program, exit_status, stdout, stdout_truncated, stderr, stderr_truncated, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        "#,
+    )
+    .bind(job_id)
+    .bind(program)
+    .bind(exit_status)
+    .bind(stdout.text)
+    .bind(stdout.truncated)
+    .bind(stderr.text)
+    .bind(stderr.truncated)
+    .bind(Utc::now())
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn select_jobs(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<Job>, Error> {
+    let job_rows = sqlx::query(
+        r#"
+            SELECT id, layer_uuid, state, error, started_at, finished_at FROM jobs
+            WHERE estate = ?1 AND layer_uuid = ?2
+            ORDER BY started_at DESC
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut jobs = Vec::with_capacity(job_rows.len());
+    for job_row in job_rows {
+        let job_id: i32 = job_row.try_get("id")?;
+        let command_rows = sqlx::query(
+            r#"
+                SELECT program, exit_status, stdout, stdout_truncated, stderr, stderr_truncated, created_at
+                FROM job_commands
+                WHERE job_id = ?1
+                ORDER BY id
+            "#,
+        )
+        .bind(job_id)
+        .fetch_all(db_pool)
+        .await?;
+
+        let mut commands = Vec::with_capacity(command_rows.len());
+        for r in command_rows {
+            commands.push(JobCommand {
+                program: r.try_get("program")?,
+                exit_status: r.try_get("exit_status")?,
+                stdout: r.try_get("stdout")?,
+                stdout_truncated: r.try_get("stdout_truncated")?,
+                stderr: r.try_get("stderr")?,
+                stderr_truncated: r.try_get("stderr_truncated")?,
+                created_at: r.try_get("created_at")?,
+            });
+        }
+
+        jobs.push(Job {
+            id: job_id,
+            layer_uuid: job_row.try_get("layer_uuid")?,
+            state: JobState::parse(job_row.try_get("state")?).unwrap_or(JobState::Failed),
+            error: job_row.try_get("error")?,
+            started_at: job_row.try_get("started_at")?,
+            finished_at: job_row.try_get("finished_at")?,
+            commands,
+        });
+    }
+    Ok(jobs)
+}
+
+pub(crate) async fn select_latest_job(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Option<JobSummary>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT id, state, started_at, finished_at FROM jobs
+            WHERE estate = ?1 AND layer_uuid = ?2
+            ORDER BY started_at DESC
+            LIMIT 1
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .fetch_optional(db_pool)
+    .await?;
+    result
+        .map(|r| {
+            Ok(JobSummary {
+                id: r.try_get("id")?,
+                state: JobState::parse(r.try_get("state")?).unwrap_or(JobState::Failed),
+                started_at: r.try_get("started_at")?,
+                finished_at: r.try_get("finished_at")?,
+            })
+        })
+        .transpose()
+}
@@ -0,0 +1,81 @@
+use sqlx::{Error, Pool, Row, Sqlite};
+
+use crate::database::JobStats;
+
+pub(crate) async fn ping(db_pool: &Pool<Sqlite>) -> Result<(), Error> {
+    sqlx::query("SELECT 1").fetch_one(db_pool).await?;
+    Ok(())
+}
+
+pub(crate) async fn count_shares_by_layer(
+    db_pool: &Pool<Sqlite>,
+) -> Result<Vec<(String, String, i64)>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT estate, layer_uuid, COUNT(share) AS count FROM shares
+            GROUP BY estate, layer_uuid
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await?;
+    result
+        .iter()
+        .map(|r| {
+            Ok((
+                r.try_get("estate")?,
+                r.try_get("layer_uuid")?,
+                r.try_get("count")?,
+            ))
+        })
+        .collect()
+}
+
+pub(crate) async fn select_job_stats(db_pool: &Pool<Sqlite>) -> Result<Vec<JobStats>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT
+                estate,
+                state,
+                COUNT(id) AS count,
+                COALESCE(SUM((julianday(finished_at) - julianday(started_at)) * 86400.0), 0.0) AS duration_sum
+            FROM jobs
+            GROUP BY estate, state
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await?;
+    result
+        .iter()
+        .map(|r| {
+            Ok(JobStats {
+                estate: r.try_get("estate")?,
+                state: r.try_get("state")?,
+                count: r.try_get("count")?,
+                duration_sum: r.try_get("duration_sum")?,
+            })
+        })
+        .collect()
+}
+
+pub(crate) async fn count_audit_events(
+    db_pool: &Pool<Sqlite>,
+    events: &[&str],
+) -> Result<Vec<(String, String, i64)>, Error> {
+    // SQLite has no arrays to bind, the few events are filtered here
+    let result = sqlx::query(
+        r#"
+            SELECT estate, event, COUNT(id) AS count FROM audit_log
+            GROUP BY estate, event
+        "#,
+    )
+    .fetch_all(db_pool)
+    .await?;
+    let mut counts = Vec::new();
+    for row in result.iter() {
+        let event: String = row.try_get("event")?;
+        if events.contains(&event.as_str()) {
+            counts.push((row.try_get("estate")?, event, row.try_get("count")?));
+        }
+    }
+    Ok(counts)
+}
@@ -0,0 +1,200 @@
+pub(crate) mod audit;
+pub(crate) mod deliveries;
+pub(crate) mod jobs;
+pub(crate) mod metrics;
+pub(crate) mod shares;
+
+use async_trait::async_trait;
+use ed25519_dalek::SigningKey;
+use sqlx::migrate::MigrateError;
+use std::str::FromStr;
+
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{Error, Pool, Sqlite};
+
+use crate::database::{JobStats, PoolStats, Storage};
+use crate::helper::output::CapturedOutput;
+use crate::models::admin::ShareRecord;
+use crate::models::audit::AuditEntry;
+use crate::models::delivery::{Delivery, DeliveryState};
+use crate::models::job::{Job, JobState, JobSummary};
+
+pub(crate) struct SqliteStorage {
+    pool: Pool<Sqlite>,
+    max_connections: u32,
+}
+
+impl SqliteStorage {
+    pub(crate) async fn connect(url: &str) -> Result<SqliteStorage, Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        // SQLite serializes writes anyway, a single connection also keeps the audit log chain
+        // consistent and sqlite::memory: databases alive for the whole run
+        let max_connections = 1;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await?;
+        Ok(SqliteStorage {
+            pool,
+            max_connections,
+        })
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn migrate(&self) -> Result<(), MigrateError> {
+        sqlx::migrate!("migrations/sqlite").run(&self.pool).await
+    }
+
+    async fn ping(&self) -> Result<(), Error> {
+        metrics::ping(&self.pool).await
+    }
+
+    fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max_connections: self.max_connections,
+        }
+    }
+
+    async fn insert_share(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+        share: String,
+    ) -> Result<bool, Error> {
+        shares::insert_share(&self.pool, estate, layer_uuid, share).await
+    }
+
+    async fn count_shares(&self, estate: &str, layer_uuid: String) -> Result<Option<i64>, Error> {
+        shares::count_shares(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn count_shares_by_layer(&self) -> Result<Vec<(String, String, i64)>, Error> {
+        metrics::count_shares_by_layer(&self.pool).await
+    }
+
+    async fn select_shares(&self, estate: &str, layer_uuid: String) -> Result<Vec<String>, Error> {
+        shares::select_shares(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn select_share_records(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+    ) -> Result<Vec<ShareRecord>, Error> {
+        shares::select_share_records(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn delete_shares(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+        share_id: Option<i32>,
+    ) -> Result<u64, Error> {
+        shares::delete_shares(&self.pool, estate, layer_uuid, share_id).await
+    }
+
+    async fn insert_job(&self, estate: &str, layer_uuid: String) -> Result<i32, Error> {
+        jobs::insert_job(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn finish_job(
+        &self,
+        job_id: i32,
+        state: JobState,
+        error: Option<String>,
+    ) -> Result<bool, Error> {
+        jobs::finish_job(&self.pool, job_id, state, error).await
+    }
+
+    async fn insert_job_command(
+        &self,
+        job_id: i32,
+        program: String,
+        exit_status: Option<i32>,
+        stdout: CapturedOutput,
+        stderr: CapturedOutput,
+    ) -> Result<bool, Error> {
+        jobs::insert_job_command(&self.pool, job_id, program, exit_status, stdout, stderr).await
+    }
+
+    async fn select_jobs(&self, estate: &str, layer_uuid: String) -> Result<Vec<Job>, Error> {
+        jobs::select_jobs(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn select_latest_job(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+    ) -> Result<Option<JobSummary>, Error> {
+        jobs::select_latest_job(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn select_job_stats(&self) -> Result<Vec<JobStats>, Error> {
+        metrics::select_job_stats(&self.pool).await
+    }
+
+    async fn insert_delivery(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+        beneficiary: String,
+        method: String,
+    ) -> Result<i32, Error> {
+        deliveries::insert_delivery(&self.pool, estate, layer_uuid, beneficiary, method).await
+    }
+
+    async fn finish_delivery(
+        &self,
+        delivery_id: i32,
+        state: DeliveryState,
+        error: Option<String>,
+    ) -> Result<bool, Error> {
+        deliveries::finish_delivery(&self.pool, delivery_id, state, error).await
+    }
+
+    async fn select_deliveries(
+        &self,
+        estate: &str,
+        layer_uuid: String,
+    ) -> Result<Vec<Delivery>, Error> {
+        deliveries::select_deliveries(&self.pool, estate, layer_uuid).await
+    }
+
+    async fn insert_audit_entry(
+        &self,
+        estate: &str,
+        signing_key: Option<&SigningKey>,
+        event: &str,
+        layer_uuid: Option<String>,
+        actor: &str,
+        details: String,
+    ) -> Result<i64, Error> {
+        audit::insert_audit_entry(
+            &self.pool,
+            estate,
+            signing_key,
+            event,
+            layer_uuid,
+            actor,
+            details,
+        )
+        .await
+    }
+
+    async fn select_audit_entries(&self, estate: &str) -> Result<Vec<AuditEntry>, Error> {
+        audit::select_audit_entries(&self.pool, estate).await
+    }
+
+    async fn count_audit_events(
+        &self,
+        events: &[&str],
+    ) -> Result<Vec<(String, String, i64)>, Error> {
+        metrics::count_audit_events(&self.pool, events).await
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::Utc;
+use sqlx::{Error, Pool, Row, Sqlite};
+
+use crate::helper::hash::sha256_hex;
+use crate::models::admin::ShareRecord;
+
+pub(crate) async fn insert_share(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+    share: String,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        r#"
+            INSERT INTO shares (estate, layer_uuid, share, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (estate, layer_uuid, share) DO NOTHING
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .bind(share)
+    .bind(Utc::now())
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+pub(crate) async fn count_shares(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Option<i64>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT COUNT(share) AS count FROM shares
+            WHERE estate = ?1 AND layer_uuid = ?2
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .fetch_one(db_pool)
+    .await?;
+    result.try_get("count")
+}
+
+pub(crate) async fn select_shares(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<String>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT share FROM shares
+            WHERE estate = ?1 AND layer_uuid = ?2
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .fetch_all(db_pool)
+    .await?;
+    result.iter().map(|r| r.try_get("share")).collect()
+}
+
+pub(crate) async fn select_share_records(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+) -> Result<Vec<ShareRecord>, Error> {
+    let result = sqlx::query(
+        r#"
+            SELECT id, share, created_at FROM shares
+            WHERE estate = ?1 AND layer_uuid = ?2
+            ORDER BY id
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .fetch_all(db_pool)
+    .await?;
+    result
+        .iter()
+        .map(|r| {
+            Ok(ShareRecord {
+                id: r.try_get("id")?,
+                sha256: sha256_hex(r.try_get::<String, _>("share")?.as_bytes()),
+                created_at: r.try_get("created_at")?,
+            })
+        })
+        .collect()
+}
+
+pub(crate) async fn delete_shares(
+    db_pool: &Pool<Sqlite>,
+    estate: &str,
+    layer_uuid: String,
+    share_id: Option<i32>,
+) -> Result<u64, Error> {
+    let result = sqlx::query(
+        r#"
+            DELETE FROM shares
+            WHERE estate = ?1 AND layer_uuid = ?2 AND (?3 IS NULL OR id = ?3)
+        "#,
+    )
+    .bind(estate)
+    .bind(layer_uuid)
+    .bind(share_id)
+    .execute(db_pool)
+    .await?;
+    Ok(result.rows_affected())
+}
@@ -1,42 +1,37 @@
+mod cleartext;
+mod liveness;
 mod models;
-
-use chrono::{DateTime, Local};
-use clap::{value_parser, Arg, Command};
+mod prometheus;
+mod signature;
+mod sources;
+
+use chrono::{DateTime, FixedOffset, Local};
+use clap::{value_parser, Arg, ArgAction, Command};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 use std::{error::Error, io::Write, path::PathBuf};
 
-use models::dms::DMS;
-
-fn verify_pgp_signature(signature: String, keyring: PathBuf) -> Result<String, Box<dyn Error>> {
-    let mut process = std::process::Command::new("gpgv")
-        .args(&[
-            "-q",
-            "--keyring",
-            keyring.to_str().unwrap(),
-            "--output",
-            "-",
-        ])
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
-        .spawn()?;
-
-    let maybe_stdin = process.stdin.take();
-    if maybe_stdin.is_none() {
-        let _ = process.kill();
-        return Err("Unable to write stdin of gpgv process".into());
-    }
-    let mut stdin = maybe_stdin.unwrap();
-    stdin.write_all(signature.as_bytes())?;
-    drop(stdin);
-
-    let output = process.wait_with_output()?;
-
-    if !output.status.success() {
-        output.stderr.iter().for_each(|b| print!("{}", *b as char));
-        return Err(format!("gpgv returned exit status {}", output.status).into());
-    }
-
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+use liveness::{Decision, Evidence};
+use models::dms::{Challenge, DMSCommand, WatchdogConfig, DMS};
+use prometheus::MetricsWriter;
+use sha2::{Digest, Sha256};
+use signature::openpgp::Keyring;
+
+/// Outcome of checking a timestamp source, reported as metrics
+struct SourceResult {
+    source: String,
+    fetched: bool,
+    verified: bool,
+    // fingerprint of the key that signed the timestamp
+    signer: Option<String>,
+    timestamp: Option<DateTime<FixedOffset>>,
+    // why the timestamp isn't plausible, f.e. "future"
+    anomaly: Option<&'static str>,
+    // "unreachable" or "no_fresh_signature" once stale_after of the source passed
+    stale: Option<&'static str>,
 }
 
 fn main() {
@@ -51,77 +46,319 @@ fn main() {
                 .default_value("dms.yml")
                 .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            Arg::new("metrics-file")
+                .short('m')
+                .long("metrics-file")
+                .help("Path to write metrics for the Prometheus textfile collector to, overrides metrics_file of the config")
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("daemon")
+                .short('d')
+                .long("daemon")
+                .help("Keep running and check every daemon.interval seconds instead of once")
+                .action(ArgAction::SetTrue),
+        )
         .get_matches();
 
     let config_file_path = matches.get_one::<PathBuf>("config").unwrap();
+    let metrics_file = matches.get_one::<PathBuf>("metrics-file");
+
+    if *matches.get_one::<bool>("daemon").unwrap_or(&false) {
+        run_daemon(config_file_path, metrics_file);
+    }
+
+    match run_cycle(config_file_path, metrics_file) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs cycles until the process is stopped, the watchdog alerts if none completes for too long.
+fn run_daemon(config_file_path: &PathBuf, metrics_file: Option<&PathBuf>) -> ! {
+    let daemon = match read_config_file(config_file_path) {
+        Ok(config) => config.daemon,
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    println!(
+        "Running as daemon, checking every {}s with up to {}s jitter.",
+        daemon.interval, daemon.jitter
+    );
 
-    let mut reader = std::fs::File::open(config_file_path).expect("Failed to open config file");
-    let mut config: DMS =
-        serde_yaml::from_reader(&mut reader).expect("Failed to parse config file");
+    let last_cycle = Arc::new(AtomicI64::new(Local::now().timestamp()));
+    if let Some(watchdog) = daemon.watchdog {
+        if watchdog.max_cycle_age <= daemon.interval + daemon.jitter {
+            println!(
+                "Warning: watchdog.max_cycle_age should exceed the interval and jitter, or the watchdog alerts between cycles"
+            );
+        }
+        let last_cycle = last_cycle.clone();
+        thread::spawn(move || run_watchdog(watchdog, last_cycle));
+    }
+
+    loop {
+        match run_cycle(config_file_path, metrics_file) {
+            Ok(_) => last_cycle.store(Local::now().timestamp(), Ordering::SeqCst),
+            Err(e) => println!("Cycle failed: {}", e),
+        }
+
+        let jitter = match daemon.jitter {
+            0 => 0,
+            jitter => rand::random::<u64>() % (jitter + 1),
+        };
+        println!("Next check in {}s.", daemon.interval + jitter);
+        thread::sleep(Duration::from_secs(daemon.interval + jitter));
+    }
+}
 
-    let mut last_valid_timestamp = config
-        .last_valid_timestamp
-        .clone()
-        .map(|ts| DateTime::parse_from_rfc3339(&ts).expect("Failed to parse last valid timestamp"));
+/// Runs the alert commands once whenever no cycle has completed for `max_cycle_age` seconds.
+fn run_watchdog(watchdog: WatchdogConfig, last_cycle: Arc<AtomicI64>) {
+    let mut alerted = false;
+    loop {
+        thread::sleep(Duration::from_secs(watchdog.max_cycle_age.clamp(1, 60)));
 
-    for timestamp_source in config.timestamp_sources.clone() {
-        let request = reqwest::blocking::get(timestamp_source.clone());
-        if request.is_err() {
-            println!("Failed to request timestamp from {}", timestamp_source);
+        let age = Local::now().timestamp() - last_cycle.load(Ordering::SeqCst);
+        if age < watchdog.max_cycle_age as i64 {
+            alerted = false;
             continue;
         }
-        let request_text = request.unwrap().text();
-        if request_text.is_err() {
-            println!("Failed to read timestamp from {}", timestamp_source);
+        if alerted {
             continue;
         }
-        let signed_timestamp = request_text.unwrap();
 
-        let verification_result =
-            verify_pgp_signature(signed_timestamp, config.pgp_keyring_file.clone());
+        println!(
+            "Watchdog: no cycle completed for {}s. Executing alert commands.",
+            age
+        );
+        match run_commands(&watchdog.commands, &[]) {
+            Ok(()) => alerted = true,
+            Err(e) => println!("Watchdog alert failed: {}", e),
+        }
+    }
+}
+
+/// Checks all sources and executes the actions whose threshold is reached.
+/// Returns whether a valid timestamp is known.
+fn run_cycle(
+    config_file_path: &PathBuf,
+    metrics_file: Option<&PathBuf>,
+) -> Result<bool, Box<dyn Error>> {
+    let mut config = read_config_file(config_file_path)?;
+    let metrics_file = metrics_file
+        .cloned()
+        .or_else(|| config.metrics_file.clone());
+    let keyring = match config.pgp_keyring_file.as_ref() {
+        Some(pgp_keyring_file) => Some(Keyring::load(pgp_keyring_file)?),
+        None => None,
+    };
+    validate_groups(&config)?;
+    let mut source_results = Vec::new();
+
+    let mut last_valid_timestamp = match config.last_valid_timestamp.as_ref() {
+        Some(ts) => Some(
+            DateTime::parse_from_rfc3339(ts)
+                .map_err(|e| format!("Failed to parse last valid timestamp \"{}\": {}", ts, e))?,
+        ),
+        None => None,
+    };
+
+    for source in config.timestamp_sources.clone() {
+        let timestamp_source = source.name();
+        source_results.push(SourceResult {
+            source: timestamp_source.clone(),
+            fetched: false,
+            verified: false,
+            signer: None,
+            timestamp: None,
+            anomaly: None,
+            stale: None,
+        });
+        let source_result = source_results.last_mut().unwrap();
+
+        let fetched = sources::source(&source.transport, Duration::from_secs(config.fetch_timeout))
+            .and_then(|transport| transport.fetch());
+        let signed_timestamps = match fetched {
+            Ok(signed_timestamps) => signed_timestamps,
+            Err(e) => {
+                println!("Failed to fetch timestamp from {}: {}", timestamp_source, e);
+                continue;
+            }
+        };
+        source_result.fetched = true;
+
+        // messages of strangers mustn't hide an older one of the owner
+        let verified =
+            signed_timestamps.iter().find_map(|signed_timestamp| {
+                match signature::verify(
+                    signed_timestamp,
+                    &source.format,
+                    keyring.as_ref(),
+                    &source.signers,
+                ) {
+                    Ok(verified) => Some(verified),
+                    Err(e) => {
+                        println!(
+                            "Failed to verify signature of source \"{}\": {}",
+                            timestamp_source, e
+                        );
+                        None
+                    }
+                }
+            });
+        let verified = match verified {
+            Some(verified) => verified,
+            None => continue,
+        };
+        println!(
+            "Timestamp of source \"{}\" signed by key {}.",
+            timestamp_source, verified.signer
+        );
+        source_result.signer = Some(verified.signer.clone());
+
+        // with a challenge the time it was issued is proven, not the time the owner claims
+        let valid_timestamp = match config.challenge.as_ref() {
+            Some(challenge) => match challenge
+                .issued
+                .iter()
+                .rev()
+                .find(|issued| issued.is_answered_by(&verified.text))
+            {
+                Some(answered) => answered.issued.clone(),
+                None => {
+                    println!(
+                        "Signed message of source \"{}\" answers no recent challenge.",
+                        timestamp_source
+                    );
+                    continue;
+                }
+            },
+            None => verified.text.trim().to_string(),
+        };
+        let valid_timestamp = valid_timestamp.as_str();
+        let valid_datetime = match DateTime::parse_from_rfc3339(valid_timestamp) {
+            Ok(valid_datetime) => valid_datetime,
+            Err(e) => {
+                println!(
+                    "Failed to parse signed timestamp \"{}\" of source \"{}\": {}",
+                    valid_timestamp, timestamp_source, e
+                );
+                continue;
+            }
+        };
+        source_result.verified = true;
+        source_result.timestamp = Some(valid_datetime);
 
-        if let Err(e) = verification_result {
+        // a signed timestamp far ahead would postpone every threshold
+        let age = Local::now().timestamp() - valid_datetime.timestamp();
+        if -age > config.plausibility.max_clock_skew as i64 {
             println!(
-                "Failed to verify signature of source \"{}\": {}",
-                timestamp_source, e
+                "Rejecting timestamp {} of source \"{}\", it is {}s in the future.",
+                valid_timestamp, timestamp_source, -age
             );
+            source_result.anomaly = Some("future");
             continue;
         }
-        let verification_result = verification_result.unwrap();
-
-        let valid_timestamp = verification_result.trim();
-        let valid_datetime =
-            DateTime::parse_from_rfc3339(valid_timestamp).expect("Failed to parse valid timestamp");
+        if let Some(max_age) = config.plausibility.max_age {
+            if age > max_age as i64 {
+                println!(
+                    "Rejecting timestamp {} of source \"{}\", it is {}s old.",
+                    valid_timestamp, timestamp_source, age
+                );
+                source_result.anomaly = Some("too_old");
+                continue;
+            }
+        }
+    }
 
-        if last_valid_timestamp.is_none() || last_valid_timestamp.unwrap() < valid_datetime {
-            config.last_valid_timestamp = Some(valid_timestamp.to_string());
-            last_valid_timestamp = Some(valid_datetime);
+    issue_challenge(config_file_path, &mut config)?;
+
+    // sources lagging far behind the newest timestamp may replay old signatures
+    let newest = source_results
+        .iter()
+        .filter(|result| result.anomaly.is_none())
+        .filter_map(|result| result.timestamp)
+        .chain(last_valid_timestamp)
+        .max();
+    if let (Some(max_backwards_jump), Some(newest)) =
+        (config.plausibility.max_backwards_jump, newest)
+    {
+        for result in source_results.iter_mut() {
+            let timestamp = match (result.timestamp, result.anomaly) {
+                (Some(timestamp), None) => timestamp,
+                _ => continue,
+            };
+            let behind = newest.signed_duration_since(timestamp).num_seconds();
+            if behind > max_backwards_jump as i64 {
+                println!(
+                    "Timestamp of source \"{}\" is {}s behind the newest valid timestamp.",
+                    result.source, behind
+                );
+                result.anomaly = Some("backwards");
+            }
+        }
+    }
+    handle_anomalies(config_file_path, &mut config, &source_results)?;
+
+    let evidence: Vec<Evidence> = config
+        .timestamp_sources
+        .iter()
+        .zip(source_results.iter())
+        .map(|(source, result)| Evidence {
+            source: &result.source,
+            group: source.group.as_deref(),
+            weight: source.weight,
+            timestamp: result.timestamp.filter(|_| result.anomaly.is_none()),
+        })
+        .collect();
+    let decision = liveness::decide(&config.source_groups, &evidence, Local::now());
+    for reason in decision.reasoning.iter() {
+        println!("{}", reason);
+    }
+    if let Some(proven) = decision.proven {
+        if last_valid_timestamp.is_none() || last_valid_timestamp.unwrap() < proven {
+            config.last_valid_timestamp = Some(proven.to_rfc3339());
+            last_valid_timestamp = Some(proven);
 
             println!(
-                "Newer valid timestamp from \"{}\": {}. Resetting triggers.",
-                timestamp_source, valid_timestamp
+                "Newer proof of life: {}. Resetting triggers.",
+                proven.to_rfc3339()
             );
 
             for action in config.threshold_actions.iter_mut() {
                 action.triggered = Some(false);
             }
-
-            update_config_file(config_file_path, &config);
         }
     }
 
-    if config.last_valid_timestamp.is_none() || last_valid_timestamp.is_none() {
-        println!("No valid timestamp found. Exiting.");
-        std::process::exit(1);
-    }
-    let last_valid_timestamp = last_valid_timestamp.unwrap();
+    check_staleness(&mut config, &mut source_results);
+    update_config_file(config_file_path, &config)?;
+
+    let last_valid_timestamp = match last_valid_timestamp {
+        Some(last_valid_timestamp) => last_valid_timestamp,
+        None => {
+            println!("No valid timestamp found.");
+            if let Some(metrics_file) = metrics_file.as_ref() {
+                write_metrics(metrics_file, &config, &source_results, &decision, None);
+            }
+            return Ok(false);
+        }
+    };
 
     println!(
         "Newest valid timestamp: {}. Checking threshold of actions…",
-        config.last_valid_timestamp.clone().unwrap(),
+        last_valid_timestamp.to_rfc3339(),
     );
 
-    'actionLoop: for action in config.threshold_actions.iter_mut() {
+    for action in config.threshold_actions.iter_mut() {
         if action.triggered.unwrap_or(false) {
             println!(
                 "Skipping action with threshold {}s because it was already triggered.",
@@ -146,64 +383,523 @@ fn main() {
             action.threshold
         );
 
-        for command in action.commands.iter_mut() {
-            println!("Executing program: {}", command.program);
-            let mut process = std::process::Command::new(command.program.clone());
-            let mut process = process
-                .args(command.args.clone())
-                .stdin(std::process::Stdio::piped())
-                .stdout(std::process::Stdio::null())
-                .stderr(std::process::Stdio::null());
+        if let Err(e) = run_commands(&action.commands, &[]) {
+            println!("{}", e);
+            continue;
+        }
+
+        println!("Commands finished successfully, marking action as triggered.");
+        action.triggered = Some(true);
+    }
+
+    update_config_file(config_file_path, &config)?;
+
+    if let Some(metrics_file) = metrics_file.as_ref() {
+        write_metrics(
+            metrics_file,
+            &config,
+            &source_results,
+            &decision,
+            Some(last_valid_timestamp.timestamp()),
+        );
+    }
+
+    Ok(true)
+}
 
-            if let Some(working_dir) = &command.working_dir {
-                process = process.current_dir(working_dir);
+// sources referring to a group that doesn't exist would silently never count
+fn validate_groups(config: &DMS) -> Result<(), Box<dyn Error>> {
+    for group in config.source_groups.iter() {
+        if group.name == liveness::UNGROUPED {
+            return Err(format!("Group name \"{}\" is reserved", liveness::UNGROUPED).into());
+        }
+        if group.min_weight == 0 {
+            return Err(
+                format!("min_weight of group \"{}\" must be at least 1", group.name).into(),
+            );
+        }
+        if !config
+            .timestamp_sources
+            .iter()
+            .any(|source| source.group.as_ref() == Some(&group.name))
+        {
+            return Err(format!("Group \"{}\" has no sources", group.name).into());
+        }
+    }
+    for source in config.timestamp_sources.iter() {
+        if let Some(group) = source.group.as_ref() {
+            if !config.source_groups.iter().any(|g| &g.name == group) {
+                return Err(format!(
+                    "Source \"{}\" is in unknown group \"{}\"",
+                    source.name(),
+                    group
+                )
+                .into());
             }
+        }
+    }
+    Ok(())
+}
 
-            let process = process.spawn();
-            if let Err(e) = process {
-                println!("Failed to execute command: {}", e);
-                continue 'actionLoop;
+/// Executes the staleness action once for every source that hasn't been reached or hasn't had a
+/// valid timestamp for stale_after seconds, and again once it recovered and became stale again.
+fn check_staleness(config: &mut DMS, source_results: &mut [SourceResult]) {
+    let now = Local::now();
+    let age = |time: Option<&String>| {
+        time.and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| now.signed_duration_since(time).num_seconds())
+    };
+
+    for (source, result) in config
+        .timestamp_sources
+        .iter()
+        .zip(source_results.iter_mut())
+    {
+        let stale_after = match source.stale_after {
+            Some(stale_after) => stale_after as i64,
+            None => continue,
+        };
+        let state = config
+            .source_states
+            .entry(result.source.clone())
+            .or_default();
+        if result.fetched {
+            state.last_fetched = Some(now.to_rfc3339());
+        }
+        if let Some(timestamp) = result.timestamp.filter(|_| result.anomaly.is_none()) {
+            if age(state.last_timestamp.as_ref())
+                .is_none_or(|age| age > now.signed_duration_since(timestamp).num_seconds())
+            {
+                state.last_timestamp = Some(timestamp.to_rfc3339());
             }
-            let mut process = process.unwrap();
-
-            if let Some(command_stdin) = &command.stdin {
-                let maybe_stdin = process.stdin.take();
-                if maybe_stdin.is_none() {
-                    let _ = process.kill();
-                    println!("Failed to get stdin of command");
-                    continue 'actionLoop;
+        }
+
+        // a source that answers but whose signatures are missing, invalid or old isn't down
+        result.stale = if age(state.last_fetched.as_ref()).is_none_or(|age| age > stale_after) {
+            Some("unreachable")
+        } else if age(state.last_timestamp.as_ref()).is_none_or(|age| age > stale_after) {
+            Some("no_fresh_signature")
+        } else {
+            None
+        };
+
+        match result.stale {
+            Some(stale) if state.alerted.as_deref() != Some(stale) => {
+                println!("Source \"{}\" is stale: {}.", result.source, stale);
+                let commands = match config.staleness_action.as_ref() {
+                    Some(action) => &action.commands,
+                    None => continue,
+                };
+                println!("Executing staleness commands.");
+                let env = [
+                    ("PERIMETR_DMS_SOURCE", result.source.clone()),
+                    ("PERIMETR_DMS_STALENESS", stale.to_string()),
+                ];
+                match run_commands(commands, &env) {
+                    Ok(()) => state.alerted = Some(stale.to_string()),
+                    Err(e) => println!("{}", e),
                 }
-                let mut stdin = maybe_stdin.unwrap();
-                let res = stdin.write_all(command_stdin.as_bytes());
-                if let Err(e) = res {
-                    let _ = process.kill();
-                    println!("Failed to write stdin of command: {}", e);
-                    continue 'actionLoop;
+            }
+            Some(_) => {}
+            None => {
+                if state.alerted.take().is_some() {
+                    println!("Source \"{}\" recovered.", result.source);
                 }
             }
+        }
+    }
+}
 
-            let exit_status = process.wait();
-            if let Err(e) = exit_status {
+/// Issues a new challenge once the period of the newest one is over and publishes the newest.
+fn issue_challenge(config_file_path: &PathBuf, config: &mut DMS) -> Result<(), Box<dyn Error>> {
+    let challenge = match config.challenge.as_mut() {
+        Some(challenge) => challenge,
+        None => return Ok(()),
+    };
+
+    let due = match challenge.issued.last() {
+        Some(newest) => match DateTime::parse_from_rfc3339(&newest.issued) {
+            Ok(issued) => {
+                Local::now().signed_duration_since(issued).num_seconds() >= challenge.period as i64
+            }
+            Err(_) => true,
+        },
+        None => true,
+    };
+    if due {
+        let nonce = hex(&rand::random::<[u8; 32]>());
+        let previous_chain = challenge
+            .issued
+            .last()
+            .map(|previous| previous.chain.clone())
+            .unwrap_or_default();
+        let chain = hex(&Sha256::digest(format!("{}{}", previous_chain, nonce)));
+        challenge.issued.push(Challenge {
+            nonce,
+            chain,
+            issued: Local::now().to_rfc3339(),
+        });
+        let excess = challenge
+            .issued
+            .len()
+            .saturating_sub(challenge.history.max(1));
+        challenge.issued.drain(..excess);
+        println!("Issued a new challenge.");
+    }
+
+    // written every run, f.e. in case the file is on a tmpfs
+    let newest = challenge.issued.last().unwrap();
+    let mut temporary_file = challenge.publish_file.clone().into_os_string();
+    temporary_file.push(".tmp");
+    std::fs::write(&temporary_file, newest.to_text())
+        .and_then(|_| std::fs::rename(&temporary_file, &challenge.publish_file))
+        .map_err(|e| {
+            format!(
+                "Failed to publish challenge to {}: {}",
+                challenge.publish_file.display(),
+                e
+            )
+        })?;
+
+    if due {
+        update_config_file(config_file_path, config)?;
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Executes the anomaly action once while anomalies persist, it is reset by a cycle without any.
+fn handle_anomalies(
+    config_file_path: &PathBuf,
+    config: &mut DMS,
+    source_results: &[SourceResult],
+) -> Result<(), Box<dyn Error>> {
+    let anomalies: Vec<String> = source_results
+        .iter()
+        .filter_map(|result| {
+            result
+                .anomaly
+                .map(|anomaly| format!("{} {}", result.source, anomaly))
+        })
+        .collect();
+    let action = match config.anomaly_action.as_mut() {
+        Some(action) => action,
+        None => return Ok(()),
+    };
+
+    if anomalies.is_empty() {
+        if action.triggered.unwrap_or(false) {
+            action.triggered = Some(false);
+            update_config_file(config_file_path, config)?;
+        }
+        return Ok(());
+    }
+    if action.triggered.unwrap_or(false) {
+        println!("Skipping anomaly action because it was already triggered.");
+        return Ok(());
+    }
+
+    println!("Anomalies detected. Executing anomaly commands.");
+    let env = [("PERIMETR_DMS_ANOMALIES", anomalies.join("\n"))];
+    if let Err(e) = run_commands(&action.commands, &env) {
+        println!("{}", e);
+        return Ok(());
+    }
+    action.triggered = Some(true);
+    update_config_file(config_file_path, config)
+}
+
+/// Executes commands one after another, stopping at the first failure.
+fn run_commands(commands: &[DMSCommand], env: &[(&str, String)]) -> Result<(), String> {
+    for command in commands.iter() {
+        println!("Executing program: {}", command.program);
+        let mut process = std::process::Command::new(command.program.clone());
+        let mut process = process
+            .args(command.args.clone())
+            .envs(env.iter().cloned())
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+
+        if let Some(working_dir) = &command.working_dir {
+            process = process.current_dir(working_dir);
+        }
+
+        let mut process = process
+            .spawn()
+            .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+        if let Some(command_stdin) = &command.stdin {
+            let maybe_stdin = process.stdin.take();
+            if maybe_stdin.is_none() {
+                let _ = process.kill();
+                return Err("Failed to get stdin of command".to_string());
+            }
+            let mut stdin = maybe_stdin.unwrap();
+            if let Err(e) = stdin.write_all(command_stdin.as_bytes()) {
                 let _ = process.kill();
-                println!("Failed to wait for command to finish: {}", e);
-                continue 'actionLoop;
+                return Err(format!("Failed to write stdin of command: {}", e));
             }
-            let exit_status = exit_status.unwrap();
+        }
+        drop(process.stdin.take());
 
-            if !exit_status.success() {
-                println!("Command failed with exit status {}", exit_status);
-                continue 'actionLoop;
+        let exit_status = match process.wait() {
+            Ok(exit_status) => exit_status,
+            Err(e) => {
+                let _ = process.kill();
+                return Err(format!("Failed to wait for command to finish: {}", e));
             }
+        };
+
+        if !exit_status.success() {
+            return Err(format!("Command failed with exit status {}", exit_status));
         }
+    }
+    Ok(())
+}
 
-        println!("Commands finished successfully, marking action as triggered.");
-        action.triggered = Some(true);
+/// Writes metrics for the Prometheus textfile collector, replacing the file atomically.
+fn write_metrics(
+    metrics_file: &PathBuf,
+    config: &DMS,
+    source_results: &[SourceResult],
+    decision: &Decision,
+    last_valid_timestamp: Option<i64>,
+) {
+    let now = Local::now().timestamp();
+    let mut metrics = MetricsWriter::new();
+
+    metrics.family(
+        "perimetr_dms_last_run_timestamp_seconds",
+        "gauge",
+        "Time of the last run of perimetr-dms",
+    );
+    metrics.sample("perimetr_dms_last_run_timestamp_seconds", &[], now as f64);
+
+    if let Some(last_valid_timestamp) = last_valid_timestamp {
+        metrics.family(
+            "perimetr_dms_last_valid_timestamp_seconds",
+            "gauge",
+            "Newest valid signed timestamp",
+        );
+        metrics.sample(
+            "perimetr_dms_last_valid_timestamp_seconds",
+            &[],
+            last_valid_timestamp as f64,
+        );
+        metrics.family(
+            "perimetr_dms_last_valid_timestamp_age_seconds",
+            "gauge",
+            "Seconds since the newest valid signed timestamp",
+        );
+        metrics.sample(
+            "perimetr_dms_last_valid_timestamp_age_seconds",
+            &[],
+            (now - last_valid_timestamp) as f64,
+        );
+    }
+
+    metrics.family(
+        "perimetr_dms_source_fetch_success",
+        "gauge",
+        "Whether the timestamp could be fetched from a source in the last run",
+    );
+    for result in source_results {
+        metrics.sample(
+            "perimetr_dms_source_fetch_success",
+            &[("source", &result.source)],
+            result.fetched as u8 as f64,
+        );
     }
+    metrics.family(
+        "perimetr_dms_source_verification_success",
+        "gauge",
+        "Whether the signature of the timestamp of a source was valid in the last run",
+    );
+    for result in source_results {
+        metrics.sample(
+            "perimetr_dms_source_verification_success",
+            &[("source", &result.source)],
+            result.verified as u8 as f64,
+        );
+    }
+    metrics.family(
+        "perimetr_dms_source_signer_info",
+        "gauge",
+        "Fingerprint of the key that signed the timestamp of a source in the last run",
+    );
+    for result in source_results {
+        if let Some(signer) = result.signer.as_ref() {
+            metrics.sample(
+                "perimetr_dms_source_signer_info",
+                &[("source", &result.source), ("fingerprint", signer)],
+                1.0,
+            );
+        }
+    }
+    metrics.family(
+        "perimetr_dms_source_timestamp_seconds",
+        "gauge",
+        "Valid signed timestamp of a source in the last run",
+    );
+    for result in source_results {
+        if let Some(timestamp) = result.timestamp {
+            metrics.sample(
+                "perimetr_dms_source_timestamp_seconds",
+                &[("source", &result.source)],
+                timestamp.timestamp() as f64,
+            );
+        }
+    }
+
+    metrics.family(
+        "perimetr_dms_source_anomaly",
+        "gauge",
+        "Implausible timestamp of a source in the last run, by kind of anomaly",
+    );
+    for result in source_results {
+        if let Some(anomaly) = result.anomaly {
+            metrics.sample(
+                "perimetr_dms_source_anomaly",
+                &[("source", &result.source), ("kind", anomaly)],
+                1.0,
+            );
+        }
+    }
+
+    metrics.family(
+        "perimetr_dms_source_stale",
+        "gauge",
+        "Stale source, by whether it was unreachable or had no fresh signature",
+    );
+    for result in source_results {
+        if let Some(reason) = result.stale {
+            metrics.sample(
+                "perimetr_dms_source_stale",
+                &[("source", &result.source), ("reason", reason)],
+                1.0,
+            );
+        }
+    }
+
+    metrics.family(
+        "perimetr_dms_liveness_proven",
+        "gauge",
+        "Whether the sources proved life in the last run",
+    );
+    metrics.sample(
+        "perimetr_dms_liveness_proven",
+        &[],
+        decision.proven.is_some() as u8 as f64,
+    );
+    metrics.family(
+        "perimetr_dms_group_fresh_weight",
+        "gauge",
+        "Summed weight of the fresh sources of a group in the last run",
+    );
+    for group in decision.groups.iter() {
+        metrics.sample(
+            "perimetr_dms_group_fresh_weight",
+            &[("group", &group.name)],
+            group.fresh_weight as f64,
+        );
+    }
+    metrics.family(
+        "perimetr_dms_group_required_weight",
+        "gauge",
+        "Weight of fresh sources a group requires",
+    );
+    for group in decision.groups.iter() {
+        metrics.sample(
+            "perimetr_dms_group_required_weight",
+            &[("group", &group.name)],
+            group.required_weight as f64,
+        );
+    }
+    metrics.family(
+        "perimetr_dms_group_satisfied",
+        "gauge",
+        "Whether the policy of a group was satisfied in the last run",
+    );
+    for group in decision.groups.iter() {
+        metrics.sample(
+            "perimetr_dms_group_satisfied",
+            &[("group", &group.name)],
+            group.proven.is_some() as u8 as f64,
+        );
+    }
+
+    metrics.family(
+        "perimetr_dms_action_threshold_reached",
+        "gauge",
+        "Whether the threshold of an action has been reached",
+    );
+    for action in config.threshold_actions.iter() {
+        let reached = last_valid_timestamp
+            .map(|timestamp| now - timestamp >= action.threshold as i64)
+            .unwrap_or(false);
+        metrics.sample(
+            "perimetr_dms_action_threshold_reached",
+            &[("threshold", &action.threshold.to_string())],
+            reached as u8 as f64,
+        );
+    }
+    metrics.family(
+        "perimetr_dms_action_triggered",
+        "gauge",
+        "Whether the commands of an action have been executed successfully",
+    );
+    for action in config.threshold_actions.iter() {
+        metrics.sample(
+            "perimetr_dms_action_triggered",
+            &[("threshold", &action.threshold.to_string())],
+            action.triggered.unwrap_or(false) as u8 as f64,
+        );
+    }
+
+    // the collector must never read a partially written file
+    let mut temporary_file = metrics_file.clone().into_os_string();
+    temporary_file.push(".tmp");
+    let result = std::fs::write(&temporary_file, metrics.finish())
+        .and_then(|_| std::fs::rename(&temporary_file, metrics_file));
+    if let Err(e) = result {
+        println!(
+            "Failed to write metrics to {}: {}",
+            metrics_file.display(),
+            e
+        );
+    }
+}
 
-    update_config_file(config_file_path, &config);
+fn read_config_file(config_file_path: &PathBuf) -> Result<DMS, Box<dyn Error>> {
+    let reader = std::fs::File::open(config_file_path)
+        .map_err(|e| format!("Failed to open {}: {}", config_file_path.display(), e))?;
+    serde_yaml::from_reader(reader)
+        .map_err(|e| format!("Failed to parse {}: {}", config_file_path.display(), e).into())
 }
 
-fn update_config_file(config_file_path: &PathBuf, config: &DMS) {
-    let mut writer = std::fs::File::create(config_file_path).expect("Failed to open config file");
-    serde_yaml::to_writer(&mut writer, &config).expect("Failed to write config file");
+fn update_config_file(config_file_path: &PathBuf, config: &DMS) -> Result<(), Box<dyn Error>> {
+    let yaml = serde_yaml::to_string(&config)
+        .map_err(|e| format!("Failed to write {}: {}", config_file_path.display(), e))?;
+
+    // an interrupted write must never leave a truncated configuration behind
+    let mut temporary_file = config_file_path.clone().into_os_string();
+    temporary_file.push(".tmp");
+    // the configuration may hold secrets, the copy gets its permissions from the start
+    std::fs::metadata(config_file_path)
+        .and_then(|metadata| {
+            let _ = std::fs::remove_file(&temporary_file);
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(metadata.permissions().mode())
+                .open(&temporary_file)
+        })
+        .and_then(|mut file| file.write_all(yaml.as_bytes()))
+        .and_then(|_| std::fs::rename(&temporary_file, config_file_path))
+        .map_err(|e| format!("Failed to write {}: {}", config_file_path.display(), e))?;
+    Ok(())
 }
@@ -0,0 +1,166 @@
+use std::error::Error;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use ed25519_dalek::{Signature, SigningKey, Verifier, VerifyingKey};
+
+use crate::helper::hash::sha256_hex;
+use crate::models::audit::AuditEntry;
+
+// prev_hash of the first entry
+pub(crate) const GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[allow(dead_code)]
+pub(crate) fn parse_signing_key(encoded: &str) -> Result<SigningKey, Box<dyn Error>> {
+    let bytes: [u8; 32] = base64::decode(encoded.trim())?
+        .try_into()
+        .map_err(|_| "Signing key must be 32 bytes")?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+#[allow(dead_code)]
+pub(crate) fn parse_public_key(encoded: &str) -> Result<VerifyingKey, Box<dyn Error>> {
+    let bytes: [u8; 32] = base64::decode(encoded.trim())?
+        .try_into()
+        .map_err(|_| "Public key must be 32 bytes")?;
+    Ok(VerifyingKey::from_bytes(&bytes)?)
+}
+
+/// Hash of an entry, over an unambiguous JSON encoding of its fields and the previous hash.
+pub(crate) fn audit_entry_hash(
+    prev_hash: &str,
+    created_at: &DateTime<Utc>,
+    event: &str,
+    layer_uuid: &Option<String>,
+    actor: &str,
+    details: &str,
+) -> String {
+    // the database stores microseconds
+    let created_at = created_at.to_rfc3339_opts(SecondsFormat::Micros, true);
+    let encoded =
+        serde_json::to_string(&(prev_hash, created_at, event, layer_uuid, actor, details))
+            .expect("Failed to encode audit entry"); // serializing strings can't fail
+    sha256_hex(encoded.as_bytes())
+}
+
+/// Verifies the hash chain and, if a public key is given, the signature of every entry.
+#[allow(dead_code)]
+pub(crate) fn verify_audit_log(
+    entries: &[AuditEntry],
+    public_key: Option<&VerifyingKey>,
+) -> Result<(), Box<dyn Error>> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+
+    for entry in entries {
+        if entry.prev_hash != prev_hash {
+            return Err(format!(
+                "Entry {} doesn't follow the previous entry, entries are missing or reordered",
+                entry.id
+            )
+            .into());
+        }
+
+        let hash = audit_entry_hash(
+            &entry.prev_hash,
+            &entry.created_at,
+            &entry.event,
+            &entry.layer_uuid,
+            &entry.actor,
+            &entry.details,
+        );
+        if hash != entry.hash {
+            return Err(format!("Entry {} has been modified", entry.id).into());
+        }
+
+        if let Some(public_key) = public_key {
+            let signature = entry
+                .signature
+                .as_ref()
+                .ok_or_else(|| format!("Entry {} isn't signed", entry.id))?;
+            let signature: [u8; 64] = base64::decode(signature)?
+                .try_into()
+                .map_err(|_| format!("Entry {} has a malformed signature", entry.id))?;
+            public_key
+                .verify(entry.hash.as_bytes(), &Signature::from_bytes(&signature))
+                .map_err(|_| format!("Entry {} has an invalid signature", entry.id))?;
+        }
+
+        prev_hash = entry.hash.clone();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Signer;
+
+    fn chain(signing_key: Option<&SigningKey>) -> Vec<AuditEntry> {
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let mut entries = Vec::new();
+        for (id, event) in ["share_submitted", "decryption_started"].iter().enumerate() {
+            let created_at = Utc::now();
+            let layer_uuid = Some("7f1c".to_string());
+            let details = "{}".to_string();
+            let hash = audit_entry_hash(
+                &prev_hash,
+                &created_at,
+                event,
+                &layer_uuid,
+                "server",
+                &details,
+            );
+            entries.push(AuditEntry {
+                id: id as i64 + 1,
+                created_at,
+                event: event.to_string(),
+                layer_uuid,
+                actor: "server".to_string(),
+                details,
+                prev_hash: prev_hash.clone(),
+                hash: hash.clone(),
+                signature: signing_key
+                    .map(|key| base64::encode(key.sign(hash.as_bytes()).to_bytes())),
+            });
+            prev_hash = hash;
+        }
+        entries
+    }
+
+    #[test]
+    fn verify_audit_log_accepts_an_intact_chain() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        assert!(verify_audit_log(&chain(None), None).is_ok());
+        assert!(verify_audit_log(&[], None).is_ok());
+        assert!(verify_audit_log(
+            &chain(Some(&signing_key)),
+            Some(&signing_key.verifying_key())
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn verify_audit_log_detects_modified_entries() {
+        let mut entries = chain(None);
+        entries[0].actor = "admin".to_string();
+        assert!(verify_audit_log(&entries, None).is_err());
+    }
+
+    #[test]
+    fn verify_audit_log_detects_missing_and_reordered_entries() {
+        let mut entries = chain(None);
+        entries.swap(0, 1);
+        assert!(verify_audit_log(&entries, None).is_err());
+        assert!(verify_audit_log(&chain(None)[1..], None).is_err());
+    }
+
+    #[test]
+    fn verify_audit_log_checks_signatures() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        let public_key = signing_key.verifying_key();
+        assert!(verify_audit_log(&chain(None), Some(&public_key)).is_err());
+        assert!(verify_audit_log(&chain(Some(&other_key)), Some(&public_key)).is_err());
+    }
+}
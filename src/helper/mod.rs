@@ -1,2 +1,5 @@
+pub(crate) mod audit;
+pub(crate) mod hash;
+pub(crate) mod output;
 pub(crate) mod strings;
 pub(crate) mod vsss;
@@ -0,0 +1,102 @@
+use std::io::{self, Read};
+use std::thread::{self, JoinHandle};
+
+// Maximum number of bytes stored per stream of a command
+#[allow(dead_code)]
+pub(crate) const MAX_CAPTURED_OUTPUT: usize = 64 * 1024;
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Default)]
+#[allow(dead_code)]
+pub(crate) struct CapturedOutput {
+    pub(crate) text: String,
+    pub(crate) truncated: bool,
+}
+
+/// Reads a stream until EOF, keeping at most `limit` bytes with every occurrence of `secret` redacted.
+/// The remainder is drained so the writing process never blocks on a full pipe.
+#[allow(dead_code)]
+pub(crate) fn capture_output<R: Read>(
+    mut reader: R,
+    limit: usize,
+    secret: &str,
+) -> io::Result<CapturedOutput> {
+    // read a bit more than the limit so a secret crossing the limit is still redacted
+    let mut buffer = Vec::new();
+    (&mut reader)
+        .take((limit + secret.len() + 1) as u64)
+        .read_to_end(&mut buffer)?;
+    let drained = io::copy(&mut reader, &mut io::sink())?;
+
+    let mut text = String::from_utf8_lossy(&buffer).to_string();
+    if !secret.is_empty() {
+        text = text.replace(secret, REDACTED);
+    }
+
+    let mut truncated = drained > 0;
+    if text.len() > limit {
+        let mut end = limit;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        truncated = true;
+    }
+
+    Ok(CapturedOutput { text, truncated })
+}
+
+/// Captures a stream on a separate thread, see `capture_output`.
+#[allow(dead_code)]
+pub(crate) fn capture_output_in_background<R: Read + Send + 'static>(
+    reader: Option<R>,
+    limit: usize,
+    secret: String,
+) -> JoinHandle<io::Result<CapturedOutput>> {
+    thread::spawn(move || match reader {
+        Some(reader) => capture_output(reader, limit, &secret),
+        None => Ok(CapturedOutput::default()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_output_redacts_the_secret() {
+        let output = capture_output(&b"key: hunter2\nagain hunter2"[..], 64, "hunter2").unwrap();
+        assert_eq!(output.text, "key: [REDACTED]\nagain [REDACTED]");
+        assert!(!output.truncated);
+    }
+
+    #[test]
+    fn capture_output_keeps_output_without_secret() {
+        let output = capture_output(&b"done"[..], 64, "").unwrap();
+        assert_eq!(output.text, "done");
+        assert!(!output.truncated);
+    }
+
+    #[test]
+    fn capture_output_truncates_at_the_limit() {
+        let output = capture_output(&[b'a'; 100][..], 10, "").unwrap();
+        assert_eq!(output.text, "a".repeat(10));
+        assert!(output.truncated);
+    }
+
+    #[test]
+    fn capture_output_redacts_a_secret_crossing_the_limit() {
+        let output = capture_output(&b"0123456hunter2 and more"[..], 10, "hunter2").unwrap();
+        assert_eq!(output.text, "0123456[RE");
+        assert!(output.truncated);
+        assert!(!output.text.contains("hun"));
+    }
+
+    #[test]
+    fn capture_output_truncates_at_a_char_boundary() {
+        let output = capture_output("aéé".as_bytes(), 2, "").unwrap();
+        assert_eq!(output.text, "a");
+        assert!(output.truncated);
+    }
+}
@@ -0,0 +1,247 @@
+use std::cmp::Reverse;
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::models::dms::SourceGroup;
+
+// sources without group
+pub(crate) const UNGROUPED: &str = "ungrouped";
+
+/// Valid timestamp of a source in a run, without one if it failed or was implausible
+pub(crate) struct Evidence<'a> {
+    pub(crate) source: &'a str,
+    pub(crate) group: Option<&'a str>,
+    pub(crate) weight: u32,
+    pub(crate) timestamp: Option<DateTime<FixedOffset>>,
+}
+
+/// Outcome of the policy of a group of sources
+pub(crate) struct GroupOutcome {
+    pub(crate) name: String,
+    pub(crate) fresh_weight: u32,
+    pub(crate) required_weight: u32,
+    // newest time confirmed by enough fresh sources
+    pub(crate) proven: Option<DateTime<FixedOffset>>,
+}
+
+/// Liveness decision of a run and why it was made
+pub(crate) struct Decision {
+    // time life was proven at, if the policies of all groups are satisfied
+    pub(crate) proven: Option<DateTime<FixedOffset>>,
+    pub(crate) groups: Vec<GroupOutcome>,
+    pub(crate) reasoning: Vec<String>,
+}
+
+/// Decides whether the timestamps of a run prove life.
+///
+/// Without groups any single source suffices. With groups the policy of every group has to be
+/// satisfied, sources without group form a group of their own that any of them satisfies.
+pub(crate) fn decide(
+    groups: &[SourceGroup],
+    evidence: &[Evidence],
+    now: DateTime<Local>,
+) -> Decision {
+    let mut policies: Vec<(String, u32, Option<u64>)> = groups
+        .iter()
+        .map(|group| (group.name.clone(), group.min_weight, group.fresh_within))
+        .collect();
+    if groups.is_empty() || evidence.iter().any(|e| e.group.is_none()) {
+        policies.push((UNGROUPED.to_string(), 1, None));
+    }
+
+    let mut decision = Decision {
+        proven: None,
+        groups: Vec::new(),
+        reasoning: Vec::new(),
+    };
+    for (name, required_weight, fresh_within) in policies {
+        let mut fresh: Vec<&Evidence> = evidence
+            .iter()
+            .filter(|e| e.group.unwrap_or(UNGROUPED) == name)
+            .filter(|e| match (e.timestamp, fresh_within) {
+                (Some(timestamp), Some(fresh_within)) => {
+                    now.signed_duration_since(timestamp).num_seconds() <= fresh_within as i64
+                }
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+            .collect();
+        fresh.sort_by_key(|e| Reverse(e.timestamp));
+
+        // the newest time that sources with enough weight confirm
+        let mut weight = 0;
+        let mut proven = None;
+        for e in fresh.iter() {
+            weight += e.weight;
+            if proven.is_none() && weight >= required_weight {
+                proven = e.timestamp;
+            }
+        }
+
+        let sources: Vec<String> = fresh
+            .iter()
+            .map(|e| format!("{} ({})", e.source, e.timestamp.unwrap().to_rfc3339()))
+            .collect();
+        let freshness = match fresh_within {
+            Some(fresh_within) => format!("fresh within {}s", fresh_within),
+            None => "valid".to_string(),
+        };
+        decision.reasoning.push(match proven {
+            Some(proven) => format!(
+                "Group \"{}\" satisfied: weight {} of required {} {} [{}], proving life at {}.",
+                name,
+                weight,
+                required_weight,
+                freshness,
+                sources.join(", "),
+                proven.to_rfc3339()
+            ),
+            None => format!(
+                "Group \"{}\" not satisfied: weight {} of required {} {} [{}].",
+                name,
+                weight,
+                required_weight,
+                freshness,
+                sources.join(", ")
+            ),
+        });
+        decision.groups.push(GroupOutcome {
+            name,
+            fresh_weight: weight,
+            required_weight,
+            proven,
+        });
+    }
+
+    // every group has to confirm the time, so the oldest of them is proven
+    if decision.groups.iter().all(|group| group.proven.is_some()) {
+        decision.proven = decision
+            .groups
+            .iter()
+            .filter_map(|group| group.proven)
+            .min();
+    }
+    decision.reasoning.push(match decision.proven {
+        Some(proven) => format!("Liveness decision: alive at {}.", proven.to_rfc3339()),
+        None => "Liveness decision: no proof of life in this run.".to_string(),
+    });
+    decision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Local> {
+        Local.timestamp_opt(1_800_000_000, 0).unwrap()
+    }
+
+    // seconds before now
+    fn at(age: i64) -> Option<DateTime<FixedOffset>> {
+        Some((now() - chrono::Duration::seconds(age)).into())
+    }
+
+    fn evidence<'a>(
+        source: &'a str,
+        group: Option<&'a str>,
+        weight: u32,
+        timestamp: Option<DateTime<FixedOffset>>,
+    ) -> Evidence<'a> {
+        Evidence {
+            source,
+            group,
+            weight,
+            timestamp,
+        }
+    }
+
+    fn group(name: &str, min_weight: u32, fresh_within: Option<u64>) -> SourceGroup {
+        SourceGroup {
+            name: name.to_string(),
+            min_weight,
+            fresh_within,
+        }
+    }
+
+    #[test]
+    fn any_source_suffices_without_groups() {
+        let decision = decide(
+            &[],
+            &[evidence("a", None, 1, None), evidence("b", None, 1, at(60))],
+            now(),
+        );
+        assert_eq!(decision.proven, at(60));
+        assert_eq!(decision.groups.len(), 1);
+        assert_eq!(decision.groups[0].name, UNGROUPED);
+
+        let decision = decide(&[], &[evidence("a", None, 1, None)], now());
+        assert_eq!(decision.proven, None);
+    }
+
+    #[test]
+    fn group_needs_enough_fresh_weight() {
+        let groups = [group("mail", 2, Some(3600))];
+        let decision = decide(
+            &groups,
+            &[
+                evidence("a", Some("mail"), 1, at(60)),
+                evidence("b", Some("mail"), 1, at(7200)),
+            ],
+            now(),
+        );
+        assert_eq!(decision.proven, None);
+        assert_eq!(decision.groups[0].fresh_weight, 1);
+
+        let decision = decide(
+            &groups,
+            &[
+                evidence("a", Some("mail"), 1, at(60)),
+                evidence("b", Some("mail"), 1, at(600)),
+            ],
+            now(),
+        );
+        // the newest time confirmed by both
+        assert_eq!(decision.proven, at(600));
+    }
+
+    #[test]
+    fn every_group_has_to_be_satisfied() {
+        let groups = [group("mail", 1, None), group("git", 2, None)];
+        let decision = decide(
+            &groups,
+            &[
+                evidence("a", Some("mail"), 1, at(60)),
+                evidence("b", Some("git"), 1, at(120)),
+            ],
+            now(),
+        );
+        assert_eq!(decision.proven, None);
+
+        let decision = decide(
+            &groups,
+            &[
+                evidence("a", Some("mail"), 1, at(60)),
+                evidence("b", Some("git"), 2, at(120)),
+            ],
+            now(),
+        );
+        // the oldest time of the groups
+        assert_eq!(decision.proven, at(120));
+    }
+
+    #[test]
+    fn ungrouped_sources_form_a_group_of_their_own() {
+        let groups = [group("mail", 1, None)];
+        let decision = decide(
+            &groups,
+            &[
+                evidence("a", Some("mail"), 1, at(60)),
+                evidence("b", None, 1, None),
+            ],
+            now(),
+        );
+        assert_eq!(decision.groups.len(), 2);
+        assert_eq!(decision.proven, None);
+    }
+}
@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::layer::LayerStatus;
+
+/// Stored share of a layer, identified by its hash instead of its content
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct ShareRecord {
+    pub(crate) id: i32,
+    // hex encoded SHA-256 hash of the share
+    pub(crate) sha256: String,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+/// Outcome of an administrative operation
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct AdminResult {
+    pub(crate) message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) layer: Option<LayerStatus>,
+}
+
+/// Layers found by a re-scan of the layer path
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct RescanReport {
+    pub(crate) layers: Vec<LayerStatus>,
+    // files with the layer suffix that couldn't be read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) errors: Vec<RescanError>,
+    // UUIDs used by more than one layer file, only the first one is served
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) duplicates: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct RescanError {
+    pub(crate) file: String,
+    pub(crate) error: String,
+}
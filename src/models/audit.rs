@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Entry of the hash-chained audit log
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct AuditEntry {
+    pub(crate) id: i64,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) event: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) layer_uuid: Option<String>,
+    // address of the client, "admin" or "server"
+    pub(crate) actor: String,
+    // JSON encoded, kept as text so the hash can be recomputed
+    pub(crate) details: String,
+    // hex encoded SHA-256 hash of the previous entry
+    pub(crate) prev_hash: String,
+    pub(crate) hash: String,
+    // base64 encoded Ed25519 signature of the hash, if the server has a signing key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) signature: Option<String>,
+}
+
+/// Complete audit log, as exported by the API
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct AuditExport {
+    // base64 encoded Ed25519 public key of the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) public_key: Option<String>,
+    pub(crate) entries: Vec<AuditEntry>,
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Delivery of decrypted files to a beneficiary
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct Delivery {
+    pub(crate) id: i32,
+    pub(crate) layer_uuid: String,
+    pub(crate) beneficiary: String,
+    pub(crate) method: String,
+    pub(crate) state: DeliveryState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    pub(crate) created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, ToSchema)]
+pub(crate) enum DeliveryState {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "delivered")]
+    Delivered,
+    #[serde(rename = "failed")]
+    Failed,
+}
+
+impl DeliveryState {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryState::Pending => "pending",
+            DeliveryState::Delivered => "delivered",
+            DeliveryState::Failed => "failed",
+        }
+    }
+
+    pub(crate) fn parse(state: &str) -> Option<DeliveryState> {
+        match state {
+            "pending" => Some(DeliveryState::Pending),
+            "delivered" => Some(DeliveryState::Delivered),
+            "failed" => Some(DeliveryState::Failed),
+            _ => None,
+        }
+    }
+}
@@ -1,15 +1,352 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 // ignore option
 
 #[derive(Serialize, Deserialize, Debug)]
+#[allow(clippy::upper_case_acronyms)]
 pub(crate) struct DMS {
-    pub(crate) timestamp_sources: Vec<String>,
-    pub(crate) pgp_keyring_file: PathBuf,
+    #[serde(deserialize_with = "deserialize_sources")]
+    pub(crate) timestamp_sources: Vec<TimestampSource>,
+    // armored or binary OpenPGP public keys, f.e. from gpg --export --armor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) pgp_keyring_file: Option<PathBuf>,
     pub(crate) threshold_actions: Vec<DMSAction>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) last_valid_timestamp: Option<String>,
+    // Prometheus textfile collector output, f.e. /var/lib/node_exporter/perimetr-dms.prom
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) metrics_file: Option<PathBuf>,
+    // seconds until fetching a timestamp source is given up
+    #[serde(default = "default_fetch_timeout")]
+    pub(crate) fetch_timeout: u64,
+    #[serde(default)]
+    pub(crate) daemon: DaemonConfig,
+    #[serde(default)]
+    pub(crate) plausibility: PlausibilityConfig,
+    // executed once while signed timestamps are implausible
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) anomaly_action: Option<AnomalyAction>,
+    // with a challenge only signed responses to published nonces count, not signed timestamps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) challenge: Option<ChallengeConfig>,
+    // without groups any single source proves life
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) source_groups: Vec<SourceGroup>,
+    // executed once for every source that becomes stale
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) staleness_action: Option<StalenessAction>,
+    // by name of the source
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) source_states: BTreeMap<String, SourceState>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ChallengeConfig {
+    // seconds until a new nonce is issued
+    #[serde(default = "default_challenge_period")]
+    pub(crate) period: u64,
+    // the owner fetches the challenge from here, f.e. a file served by a webserver
+    pub(crate) publish_file: PathBuf,
+    // number of recent challenges a response may answer
+    #[serde(default = "default_challenge_history")]
+    pub(crate) history: usize,
+    // issued challenges, newest last
+    #[serde(default)]
+    pub(crate) issued: Vec<Challenge>,
+}
+
+/// Random nonce published by the DMS, a signed response proves life after it was issued.
+///
+/// `chain` is the SHA-256 of the previous chain and the nonce, a response may sign either.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Challenge {
+    pub(crate) nonce: String,
+    pub(crate) chain: String,
+    // RFC 3339
+    pub(crate) issued: String,
+}
+
+impl Challenge {
+    const HEADER: &'static str = "perimetr-dms-challenge";
+    const RESPONSE_PREFIX: &'static str = "perimetr-dms-response ";
+
+    /// Text published for the owner to fetch.
+    pub(crate) fn to_text(&self) -> String {
+        format!(
+            "{}\nnonce: {}\nchain: {}\nissued: {}\n",
+            Challenge::HEADER,
+            self.nonce,
+            self.chain,
+            self.issued
+        )
+    }
+
+    pub(crate) fn parse(text: &str) -> Result<Challenge, String> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(Challenge::HEADER) {
+            return Err("Not a perimetr-dms challenge".to_string());
+        }
+        let (mut nonce, mut chain, mut issued) = (None, None, None);
+        for line in lines {
+            match line.split_once(": ") {
+                Some(("nonce", value)) => nonce = Some(value.trim().to_string()),
+                Some(("chain", value)) => chain = Some(value.trim().to_string()),
+                Some(("issued", value)) => issued = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+        Ok(Challenge {
+            nonce: nonce.ok_or("Challenge without nonce")?,
+            chain: chain.ok_or("Challenge without chain")?,
+            issued: issued.ok_or("Challenge without issued")?,
+        })
+    }
+
+    /// Text the owner signs to answer the challenge.
+    pub(crate) fn response(&self) -> String {
+        format!("{}{}\n", Challenge::RESPONSE_PREFIX, self.chain)
+    }
+
+    /// Whether a line of a signed text, f.e. of a commit message, is a response.
+    pub(crate) fn is_response_line(line: &str) -> bool {
+        line.trim().starts_with(Challenge::RESPONSE_PREFIX)
+    }
+
+    pub(crate) fn is_answered_by(&self, signed_text: &str) -> bool {
+        match signed_text.trim().strip_prefix(Challenge::RESPONSE_PREFIX) {
+            Some(answer) => {
+                let answer = answer.trim();
+                answer.eq_ignore_ascii_case(&self.nonce) || answer.eq_ignore_ascii_case(&self.chain)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Bounds of signed timestamps, implausible ones aren't used and are reported as anomalies
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct PlausibilityConfig {
+    // seconds a timestamp may be ahead of the local clock
+    pub(crate) max_clock_skew: u64,
+    // seconds a timestamp may be old
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_age: Option<u64>,
+    // seconds a source may be behind the newest valid timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_backwards_jump: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct AnomalyAction {
+    pub(crate) commands: Vec<DMSCommand>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) triggered: Option<bool>,
+}
+
+/// Where signed timestamps are fetched from, with the fingerprints of the keys allowed to sign them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct TimestampSource {
+    #[serde(flatten)]
+    pub(crate) transport: SourceTransport,
+    #[serde(flatten)]
+    pub(crate) format: SignatureFormat,
+    // fingerprint of an OpenPGP primary key or signing subkey, SSH principal or key fingerprint
+    // or minisign key ID, without signers any key of the format is accepted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) signers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) group: Option<String>,
+    // counted towards min_weight of the group
+    #[serde(default = "default_weight")]
+    pub(crate) weight: u32,
+    // seconds without a successful fetch or a valid timestamp until the staleness action runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) stale_after: Option<u64>,
+}
+
+/// Policy of a group of sources: sources with at least `min_weight` have to be fresh
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SourceGroup {
+    pub(crate) name: String,
+    #[serde(default = "default_weight")]
+    pub(crate) min_weight: u32,
+    // seconds a timestamp counts as fresh, any valid timestamp does without
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) fresh_within: Option<u64>,
+}
+
+/// What perimetr-dms remembers about a source with stale_after
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct SourceState {
+    // RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_fetched: Option<String>,
+    // newest valid timestamp, RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_timestamp: Option<String>,
+    // staleness the action ran for, until the source recovers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) alerted: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct StalenessAction {
+    pub(crate) commands: Vec<DMSCommand>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum SourceTransport {
+    Http {
+        url: String,
+    },
+    // a file, or the newest file of a directory
+    File {
+        path: PathBuf,
+        // only files of the directory ending with it, f.e. .asc
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        suffix: Option<String>,
+    },
+    // the signed commit a reference of a local repository points to
+    Git {
+        repository: PathBuf,
+        #[serde(default = "default_git_reference")]
+        reference: String,
+    },
+    // the newest message with a clearsigned text
+    Maildir {
+        path: PathBuf,
+        // only messages with a subject containing it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subject: Option<String>,
+    },
+    // TXT record with the base64 encoded clearsigned text
+    Dns {
+        name: String,
+        // f.e. 9.9.9.9 or 9.9.9.9:53, the resolvers of the system otherwise
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resolver: Option<String>,
+    },
+}
+
+/// How the timestamps of a source are signed and the keys accepted
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub(crate) enum SignatureFormat {
+    // clearsigned, or signed commits, with a key of pgp_keyring_file
+    Openpgp,
+    // followed by a signature of ssh-keygen -Y sign
+    Ssh {
+        allowed_signers: PathBuf,
+        #[serde(default = "default_ssh_namespace")]
+        namespace: String,
+    },
+    // followed by a signature of minisign or signify
+    Minisign {
+        public_keys: Vec<String>,
+    },
+}
+
+impl TimestampSource {
+    /// Identifies the source in logs and metrics.
+    pub(crate) fn name(&self) -> String {
+        match &self.transport {
+            SourceTransport::Http { url } => url.clone(),
+            SourceTransport::File { path, .. } => format!("file:{}", path.display()),
+            SourceTransport::Git {
+                repository,
+                reference,
+            } => format!("git:{}#{}", repository.display(), reference),
+            SourceTransport::Maildir { path, .. } => format!("maildir:{}", path.display()),
+            SourceTransport::Dns { name, .. } => format!("dns:{}", name),
+        }
+    }
+}
+
+// plain strings and sources without type are URLs fetched over HTTP, as before other types existed,
+// sources without format are signed with OpenPGP
+fn deserialize_sources<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<TimestampSource>, D::Error> {
+    Vec::<serde_yaml::Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|value| {
+            let value = match value {
+                serde_yaml::Value::String(url) => {
+                    let mut mapping = serde_yaml::Mapping::new();
+                    mapping.insert("url".into(), url.into());
+                    mapping.insert("type".into(), "http".into());
+                    mapping.insert("format".into(), "openpgp".into());
+                    mapping.into()
+                }
+                serde_yaml::Value::Mapping(mut mapping) => {
+                    if !mapping.contains_key("type") {
+                        mapping.insert("type".into(), "http".into());
+                    }
+                    if !mapping.contains_key("format") {
+                        mapping.insert("format".into(), "openpgp".into());
+                    }
+                    // git signs commits with SSH keys in the namespace git
+                    if mapping.get("type") == Some(&"git".into())
+                        && mapping.get("format") == Some(&"ssh".into())
+                    {
+                        match mapping.get("namespace") {
+                            None => {
+                                mapping.insert("namespace".into(), "git".into());
+                            }
+                            Some(namespace) if namespace != &serde_yaml::Value::from("git") => {
+                                return Err(D::Error::custom(
+                                    "SSH signatures of git commits have the namespace \"git\"",
+                                ));
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    mapping.into()
+                }
+                value => value,
+            };
+            serde_yaml::from_value(value).map_err(D::Error::custom)
+        })
+        .collect()
+}
+
+fn default_git_reference() -> String {
+    "HEAD".to_string()
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_ssh_namespace() -> String {
+    "perimetr-dms".to_string()
+}
+
+/// Schedule of perimetr-dms --daemon
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct DaemonConfig {
+    // seconds between the checks
+    pub(crate) interval: u64,
+    // up to this many seconds are added to every interval at random
+    pub(crate) jitter: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) watchdog: Option<WatchdogConfig>,
+}
+
+/// Alert if the daemon itself stops completing checks, f.e. because a source or command hangs
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WatchdogConfig {
+    // seconds without a completed check before the commands are executed
+    pub(crate) max_cycle_age: u64,
+    pub(crate) commands: Vec<DMSCommand>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -29,3 +366,144 @@ pub(crate) struct DMSCommand {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) working_dir: Option<String>,
 }
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            interval: 3600,
+            jitter: 0,
+            watchdog: None,
+        }
+    }
+}
+
+impl Default for PlausibilityConfig {
+    fn default() -> Self {
+        PlausibilityConfig {
+            max_clock_skew: 300,
+            max_age: None,
+            max_backwards_jump: None,
+        }
+    }
+}
+
+fn default_fetch_timeout() -> u64 {
+    30
+}
+
+fn default_challenge_period() -> u64 {
+    86400
+}
+
+fn default_challenge_history() -> usize {
+    7
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Sources {
+        #[serde(deserialize_with = "deserialize_sources")]
+        timestamp_sources: Vec<TimestampSource>,
+    }
+
+    fn sources(yaml: &str) -> Result<Vec<TimestampSource>, serde_yaml::Error> {
+        serde_yaml::from_str::<Sources>(yaml).map(|sources| sources.timestamp_sources)
+    }
+
+    fn challenge() -> Challenge {
+        Challenge {
+            nonce: "9f86d081884c7d65".to_string(),
+            chain: "2c26b46b68ffc68f".to_string(),
+            issued: "2026-10-18T12:00:00+00:00".to_string(),
+        }
+    }
+
+    #[test]
+    fn challenge_survives_publishing() {
+        let parsed = Challenge::parse(&challenge().to_text()).unwrap();
+        assert_eq!(parsed.nonce, challenge().nonce);
+        assert_eq!(parsed.chain, challenge().chain);
+        assert_eq!(parsed.issued, challenge().issued);
+        assert!(Challenge::parse("nonce: 9f86d081884c7d65\n").is_err());
+    }
+
+    #[test]
+    fn challenge_is_answered_by_its_nonce_or_chain() {
+        let challenge = challenge();
+        assert!(challenge.is_answered_by(&challenge.response()));
+        assert!(challenge.is_answered_by("perimetr-dms-response 9F86D081884C7D65\r\n"));
+        assert!(challenge.is_answered_by("  perimetr-dms-response 2c26b46b68ffc68f "));
+    }
+
+    #[test]
+    fn challenge_isnt_answered_by_other_texts() {
+        let challenge = challenge();
+        assert!(!challenge.is_answered_by("perimetr-dms-response 0000"));
+        assert!(!challenge.is_answered_by("perimetr-dms-response "));
+        assert!(!challenge.is_answered_by("9f86d081884c7d65"));
+        assert!(!challenge.is_answered_by("perimetr-dms-response 9f86d081884c7d65 and more"));
+        assert!(Challenge::is_response_line("perimetr-dms-response 9f86"));
+        assert!(!Challenge::is_response_line("alive"));
+    }
+
+    #[test]
+    fn plain_strings_are_http_sources_signed_with_openpgp() {
+        let sources = sources(
+            "timestamp_sources:
+  - https://example.net/alive.asc
+  - url: https://example.org/alive.asc
+    signers: [ABCD]
+",
+        )
+        .unwrap();
+        assert_eq!(sources.len(), 2);
+        for source in sources.iter() {
+            assert!(matches!(source.transport, SourceTransport::Http { .. }));
+            assert!(matches!(source.format, SignatureFormat::Openpgp));
+        }
+        assert_eq!(sources[0].name(), "https://example.net/alive.asc");
+        assert_eq!(sources[1].signers, vec!["ABCD"]);
+    }
+
+    #[test]
+    fn ssh_namespace_of_git_sources_defaults_to_git() {
+        let sources = sources(
+            "timestamp_sources:
+  - type: git
+    repository: /srv/alive.git
+    format: ssh
+    allowed_signers: /etc/perimetr/allowed_signers
+  - type: file
+    path: /srv/alive.txt
+    format: ssh
+    allowed_signers: /etc/perimetr/allowed_signers
+",
+        )
+        .unwrap();
+        let namespaces: Vec<&str> = sources
+            .iter()
+            .map(|source| match &source.format {
+                SignatureFormat::Ssh { namespace, .. } => namespace.as_str(),
+                _ => panic!("Source isn't signed with SSH"),
+            })
+            .collect();
+        assert_eq!(namespaces, vec!["git", "perimetr-dms"]);
+    }
+
+    #[test]
+    fn ssh_namespace_of_git_sources_must_be_git() {
+        assert!(sources(
+            "timestamp_sources:
+  - type: git
+    repository: /srv/alive.git
+    format: ssh
+    allowed_signers: /etc/perimetr/allowed_signers
+    namespace: perimetr-dms
+",
+        )
+        .is_err());
+    }
+}
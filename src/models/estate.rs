@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// estate of a server without configured estates, also owns data from before estates existed
+pub(crate) const DEFAULT_ESTATE: &str = "default";
+
+/// Public information about an estate, shown on its share page
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub(crate) struct EstateInfo {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    // names of the secret keepers, so they recognize the estate they provide their share to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) keepers: Vec<String>,
+    pub(crate) branding: Branding,
+}
+
+/// Appearance of the share page of an estate
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Branding {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) title: Option<String>,
+    // shown above the share form
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) logo_url: Option<String>,
+    // CSS color, f.e. #2a6f97
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) accent_color: Option<String>,
+}
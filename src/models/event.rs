@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::job::JobState;
+use crate::models::layer::{LayerState, LayerStatus};
+
+/// Change of a layer, published to subscribers of the event stream
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum LayerEvent {
+    // first event of every subscription
+    Snapshot {
+        layers: Vec<LayerStatus>,
+    },
+    ShareReceived {
+        uuid: String,
+        shares_received: i64,
+        shares_required: u8,
+    },
+    // malformed or invalid share, a sign of someone guessing
+    ShareRejected {
+        uuid: String,
+        reason: String,
+        locked_out: bool,
+    },
+    StateChanged {
+        uuid: String,
+        state: LayerState,
+    },
+    // the commands are only listed in the jobs of the admin API
+    DecryptionProgress {
+        uuid: String,
+        job_id: i32,
+        step: usize,
+        steps: usize,
+    },
+    JobFinished {
+        uuid: String,
+        job_id: i32,
+        state: JobState,
+    },
+    // once all beneficiaries are served, who they are is only in the deliveries of the admin API
+    DeliveryFinished {
+        uuid: String,
+        beneficiaries: usize,
+        failed: usize,
+    },
+}
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Readiness of the server, with the result of every check
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct Health {
+    // "ok" or "unavailable"
+    pub(crate) status: String,
+    pub(crate) checks: Vec<HealthCheck>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct HealthCheck {
+    pub(crate) name: String,
+    pub(crate) ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct Job {
+    pub(crate) id: i32,
+    pub(crate) layer_uuid: String,
+    pub(crate) state: JobState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    pub(crate) started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) finished_at: Option<DateTime<Utc>>,
+    pub(crate) commands: Vec<JobCommand>,
+}
+
+/// Job without command output, safe to expose publicly
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub(crate) struct JobSummary {
+    pub(crate) id: i32,
+    pub(crate) state: JobState,
+    pub(crate) started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, ToSchema)]
+pub(crate) enum JobState {
+    #[serde(rename = "running")]
+    Running,
+    #[serde(rename = "succeeded")]
+    Succeeded,
+    #[serde(rename = "failed")]
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct JobCommand {
+    pub(crate) program: String,
+    // None if the process was terminated by a signal or the command is a built-in action
+    pub(crate) exit_status: Option<i32>,
+    pub(crate) stdout: String,
+    pub(crate) stdout_truncated: bool,
+    pub(crate) stderr: String,
+    pub(crate) stderr_truncated: bool,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+impl JobState {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+        }
+    }
+
+    pub(crate) fn parse(state: &str) -> Option<JobState> {
+        match state {
+            "running" => Some(JobState::Running),
+            "succeeded" => Some(JobState::Succeeded),
+            "failed" => Some(JobState::Failed),
+            _ => None,
+        }
+    }
+}
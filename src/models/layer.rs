@@ -1,19 +1,42 @@
-use std::{error::Error, path::PathBuf};
+use std::{
+    error::Error,
+    path::{Component, Path, PathBuf},
+};
 
 use bls12_381_plus::{G1Projective, Scalar};
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
 use vsss_rs::FeldmanVerifier;
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::models::job::JobSummary;
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub(crate) struct Layer {
     pub(crate) uuid: String,
     pub(crate) state: LayerState,
+    #[serde(deserialize_with = "deserialize_commands")]
     pub(crate) commands: Vec<LayerCommands>,
+    // {output_dir} of the commands, relative to the directory of the layer file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output_dir: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) vsss: Option<VSSSMetadata>,
+    // verifies the secret of layers with a threshold of 1, never served by the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub(crate) secret_verifier: Option<SecretVerifier>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) unlocks: Vec<LayerUnlock>,
+    #[serde(default)]
+    pub(crate) access: LayerAccess,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) beneficiaries: Vec<Beneficiary>,
+    // only honored if the server allows sandbox settings of layers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sandbox: Option<LayerSandbox>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, ToSchema)]
 pub(crate) enum LayerState {
     #[serde(rename = "idle")]
     Idle,
@@ -21,33 +44,593 @@ pub(crate) enum LayerState {
     Decrypting,
     #[serde(rename = "decrypted")]
     Decrypted,
+    // taken out of service by an administrator, refuses shares
+    #[serde(rename = "retired")]
+    Retired,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub(crate) struct LayerCommands {
-    pub(crate) program: String,
-    pub(crate) args: Vec<String>,
-    pub(crate) working_dir: String,
-    pub(crate) secret_stdin: bool,
+/// Step of the decryption of a layer
+// Paths are relative to the directory of the layer file and must not leave it
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub(crate) enum LayerCommands {
+    // runs a program, in the sandbox if enabled
+    Exec {
+        program: String,
+        args: Vec<String>,
+        working_dir: String,
+        #[serde(default)]
+        #[schema(value_type = Object)]
+        secret: SecretDelivery,
+    },
+    // symmetrically encrypted OpenPGP file, f.e. from gpg --symmetric, with the secret as passphrase
+    DecryptOpenpgp {
+        input: String,
+        output: String,
+    },
+    // age file encrypted with the secret as passphrase
+    DecryptAge {
+        input: String,
+        output: String,
+    },
+    // tar archive, optionally compressed with zstd
+    Extract {
+        input: String,
+        #[serde(default = "default_destination")]
+        destination: String,
+    },
+    // overwrites files with random data before removing them, directories recursively
+    SecureDelete {
+        paths: Vec<String>,
+    },
+    VerifyChecksum {
+        path: String,
+        // hex encoded
+        sha256: String,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// How an exec action receives the secret
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub(crate) enum SecretDelivery {
+    #[default]
+    None,
+    Stdin,
+    // file on a private tmpfs, removed afterwards, its path is {secret_file}
+    File,
+    // named pipe written once, its path is {secret_file}
+    Pipe,
+    Env {
+        name: String,
+    },
+    // inherited file descriptor, read until EOF
+    Fd {
+        fd: i32,
+    },
+}
+
+impl LayerCommands {
+    /// Program or action name, as shown in jobs and progress events
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            LayerCommands::Exec { program, .. } => program,
+            LayerCommands::DecryptOpenpgp { .. } => "decrypt_openpgp",
+            LayerCommands::DecryptAge { .. } => "decrypt_age",
+            LayerCommands::Extract { .. } => "extract",
+            LayerCommands::SecureDelete { .. } => "secure_delete",
+            LayerCommands::VerifyChecksum { .. } => "verify_checksum",
+        }
+    }
+
+    /// Replaces the placeholders in the arguments, working directory and paths of the command.
+    pub(crate) fn expand(&self, placeholders: &Placeholders) -> Result<LayerCommands, String> {
+        let expand = |template: &String| placeholders.expand(template);
+        let expand_all = |templates: &Vec<String>| {
+            templates
+                .iter()
+                .map(expand)
+                .collect::<Result<Vec<String>, String>>()
+        };
+        Ok(match self {
+            LayerCommands::Exec {
+                program,
+                args,
+                working_dir,
+                secret,
+            } => LayerCommands::Exec {
+                program: program.clone(),
+                args: expand_all(args)?,
+                working_dir: expand(working_dir)?,
+                secret: secret.clone(),
+            },
+            LayerCommands::DecryptOpenpgp { input, output } => LayerCommands::DecryptOpenpgp {
+                input: expand(input)?,
+                output: expand(output)?,
+            },
+            LayerCommands::DecryptAge { input, output } => LayerCommands::DecryptAge {
+                input: expand(input)?,
+                output: expand(output)?,
+            },
+            LayerCommands::Extract { input, destination } => LayerCommands::Extract {
+                input: expand(input)?,
+                destination: expand(destination)?,
+            },
+            LayerCommands::SecureDelete { paths } => LayerCommands::SecureDelete {
+                paths: expand_all(paths)?,
+            },
+            LayerCommands::VerifyChecksum { path, sha256 } => LayerCommands::VerifyChecksum {
+                path: expand(path)?,
+                sha256: sha256.clone(),
+            },
+        })
+    }
+}
+
+/// Values of the placeholders in layer commands, f.e. {uuid}
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Placeholders {
+    pub(crate) uuid: String,
+    // directory of the layer file
+    pub(crate) layer_dir: String,
+    // empty directory of the decryption run, removed afterwards
+    pub(crate) scratch_dir: String,
+    pub(crate) output_dir: String,
+    // file or named pipe of the secret, only set for exec actions receiving it that way
+    pub(crate) secret_file: Option<String>,
+}
+
+impl Placeholders {
+    fn get(&self, name: &str) -> Result<&str, String> {
+        match name {
+            "uuid" => Ok(&self.uuid),
+            "layer_dir" => Ok(&self.layer_dir),
+            "scratch_dir" => Ok(&self.scratch_dir),
+            "output_dir" => Ok(&self.output_dir),
+            "secret_file" => self.secret_file.as_deref().ok_or_else(|| {
+                "Placeholder {secret_file} needs an exec action with the secret mode file or pipe"
+                    .to_string()
+            }),
+            _ => Err(format!("Unknown placeholder {{{}}}", name)),
+        }
+    }
+
+    /// Replaces {name} with the value of the placeholder, {{ and }} are literal braces.
+    pub(crate) fn expand(&self, template: &str) -> Result<String, String> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    expanded.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    expanded.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("Unclosed {{ in \"{}\"", template));
+                    }
+                    match self.get(&name) {
+                        Ok(value) => expanded.push_str(value),
+                        Err(e) => return Err(format!("{} in \"{}\"", e, template)),
+                    }
+                }
+                '}' => return Err(format!("Unmatched }} in \"{}\"", template)),
+                c => expanded.push(c),
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+fn default_destination() -> String {
+    ".".to_string()
+}
+
+// commands of layer files written before actions existed have no action tag and run a program,
+// those written before secret delivery modes existed have secret_stdin,
+// unknown placeholders and {secret_file} of commands not receiving the secret as a file are
+// rejected when the layer is read instead of when it is decrypted
+fn deserialize_commands<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<LayerCommands>, D::Error> {
+    Vec::<serde_yaml::Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|mut value| {
+            if let serde_yaml::Value::Mapping(mapping) = &mut value {
+                if !mapping.contains_key("action") {
+                    mapping.insert("action".into(), "exec".into());
+                }
+                if let Some(secret_stdin) = mapping.remove("secret_stdin") {
+                    if !mapping.contains_key("secret") {
+                        let mode = match secret_stdin.as_bool() {
+                            Some(true) => "stdin",
+                            _ => "none",
+                        };
+                        let mut secret = serde_yaml::Mapping::new();
+                        secret.insert("mode".into(), mode.into());
+                        mapping.insert("secret".into(), secret.into());
+                    }
+                }
+            }
+            let command: LayerCommands = serde_yaml::from_value(value).map_err(D::Error::custom)?;
+            let secret_file = match &command {
+                LayerCommands::Exec {
+                    secret: SecretDelivery::File | SecretDelivery::Pipe,
+                    ..
+                } => Some(String::new()),
+                _ => None,
+            };
+            command
+                .expand(&Placeholders {
+                    secret_file,
+                    ..Placeholders::default()
+                })
+                .map_err(D::Error::custom)?;
+            Ok(command)
+        })
+        .collect()
+}
+
+/// Sandbox settings of a layer, relaxing the sandbox of the server
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LayerSandbox {
+    #[serde(default = "default_sandbox_enabled")]
+    pub(crate) enabled: bool,
+    #[serde(default)]
+    pub(crate) network: bool,
+    // mounted read-only in addition to the paths of the server
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) read_only_paths: Vec<String>,
+}
+
+fn default_sandbox_enabled() -> bool {
+    true
+}
+
+/// Rules for downloading files from the directory of a layer
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+pub(crate) struct LayerAccess {
+    #[serde(default)]
+    pub(crate) visibility: LayerVisibility,
+    // files and subdirectories visible without a token, all if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) tokens: Vec<DownloadToken>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy, ToSchema)]
+pub(crate) enum LayerVisibility {
+    #[serde(rename = "public")]
+    Public,
+    // nothing until the layer has been decrypted
+    #[serde(rename = "decrypted")]
+    Decrypted,
+    // only with a download token or a signed link
+    #[default]
+    #[serde(rename = "private")]
+    Private,
+}
+
+/// Download token of a beneficiary, valid once the layer has been decrypted
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct DownloadToken {
+    pub(crate) name: String,
+    // hex encoded SHA-256 hash of the token
+    pub(crate) sha256: String,
+    // files and subdirectories available with this token, all if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) files: Vec<String>,
+}
+
+/// Recipient of decrypted files, served after a successful decryption
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub(crate) struct Beneficiary {
+    pub(crate) name: String,
+    // files and subdirectories to deliver, the output_dir of the layer if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) files: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) message: Option<String>,
+    pub(crate) delivery: DeliveryMethod,
+}
+
+// Paths are relative to the directory of the layer file
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub(crate) enum DeliveryMethod {
+    // attach files to an email sent through the configured SMTP relay
+    Email { to: String },
+    // copy files into a directory
+    Directory { path: String },
+    // encrypt files to an OpenPGP public key and place them in a directory
+    Encrypt { public_key: String, path: String },
+}
+
+impl DeliveryMethod {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            DeliveryMethod::Email { .. } => "email",
+            DeliveryMethod::Directory { .. } => "directory",
+            DeliveryMethod::Encrypt { .. } => "encrypt",
+        }
+    }
+}
+
+/// Share or secret for an inner layer, found in the decrypted output of this layer
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct LayerUnlock {
+    // UUID of the inner layer
+    pub(crate) layer: String,
+    // relative to the directory of the layer file
+    #[serde(deserialize_with = "deserialize_share_file")]
+    pub(crate) share_file: String,
+}
+
+// the layer directory isn't known yet, symlinks are checked when the share is read
+fn deserialize_share_file<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let share_file = String::deserialize(deserializer)?;
+    let path = Path::new(&share_file);
+    if share_file.is_empty()
+        || path.is_absolute()
+        || path
+            .components()
+            .any(|component| component == Component::ParentDir)
+    {
+        return Err(D::Error::custom(format!(
+            "share_file \"{}\" must be a path inside the layer directory",
+            share_file
+        )));
+    }
+    Ok(share_file)
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub(crate) struct VSSSMetadata {
     pub(crate) threshold: u8,
+    #[schema(value_type = Object)]
     pub(crate) feldman_verifier: FeldmanVerifier<Scalar, G1Projective>,
 }
 
+/// Salted hash of the secret of a layer without VSSS
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SecretVerifier {
+    // base64 encoded
+    pub(crate) salt: String,
+    // hex encoded SHA-256 hash of salt and secret
+    pub(crate) sha256: String,
+}
+
+/// Public metadata of a layer, as listed by the API
+// commands, access rules, beneficiaries and verifiers of the layer file stay on the server
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct LayerSummary {
+    pub(crate) uuid: String,
+    pub(crate) state: LayerState,
+    // shares required for the decryption, 1 for layers without VSSS
+    pub(crate) threshold: u8,
+}
+
+impl From<&Layer> for LayerSummary {
+    fn from(layer: &Layer) -> LayerSummary {
+        LayerSummary {
+            uuid: layer.uuid.clone(),
+            state: layer.state,
+            threshold: layer.vsss.as_ref().map_or(1, |vsss| vsss.threshold),
+        }
+    }
+}
+
+/// Public progress of a layer, as reported by the API
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub(crate) struct LayerStatus {
+    pub(crate) uuid: String,
+    pub(crate) state: LayerState,
+    pub(crate) shares_received: i64,
+    pub(crate) shares_required: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) job: Option<JobSummary>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub(crate) struct ShareAccepted {
+    pub(crate) message: String,
+    pub(crate) layer: LayerStatus,
+}
+
 impl Layer {
-    #[allow(dead_code)]
+    /// Placeholders of the commands, the same for the server and the CLI
+    pub(crate) fn placeholders(&self, layer_dir: &Path, scratch_dir: &Path) -> Placeholders {
+        Placeholders {
+            uuid: self.uuid.clone(),
+            layer_dir: layer_dir.display().to_string(),
+            scratch_dir: scratch_dir.display().to_string(),
+            output_dir: match self.output_dir.as_ref() {
+                Some(output_dir) => layer_dir.join(output_dir).display().to_string(),
+                None => layer_dir.display().to_string(),
+            },
+            secret_file: None,
+        }
+    }
+
     pub(crate) fn read_metadata(metadata_file: &PathBuf) -> Result<Layer, Box<dyn Error>> {
         let mut reader = std::fs::File::open(metadata_file)?;
         Ok(serde_yaml::from_reader(&mut reader)?)
     }
 
-    #[allow(dead_code)]
     pub(crate) fn write_metadata(&self, metadata_file: &PathBuf) -> Result<(), Box<dyn Error>> {
         let mut writer = std::fs::File::create(metadata_file)?;
         serde_yaml::to_writer(&mut writer, self)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders() -> Placeholders {
+        Placeholders {
+            uuid: "7f1c".to_string(),
+            layer_dir: "/layers/a".to_string(),
+            scratch_dir: "/layers/a/.scratch-1".to_string(),
+            output_dir: "/layers/a/out".to_string(),
+            secret_file: None,
+        }
+    }
+
+    #[test]
+    fn expand_replaces_placeholders() {
+        assert_eq!(
+            placeholders().expand("{output_dir}/{uuid}.tar").unwrap(),
+            "/layers/a/out/7f1c.tar"
+        );
+        assert_eq!(placeholders().expand("plain").unwrap(), "plain");
+    }
+
+    #[test]
+    fn expand_unescapes_doubled_braces() {
+        assert_eq!(
+            placeholders().expand("{{uuid}} is {uuid}").unwrap(),
+            "{uuid} is 7f1c"
+        );
+        assert_eq!(placeholders().expand("}}{{").unwrap(), "}{");
+    }
+
+    #[test]
+    fn expand_rejects_malformed_templates() {
+        assert!(placeholders().expand("{home}").is_err());
+        assert!(placeholders().expand("{uuid").is_err());
+        assert!(placeholders().expand("uuid}").is_err());
+        assert!(placeholders().expand("{}").is_err());
+    }
+
+    fn commands(yaml: &str) -> Result<Vec<LayerCommands>, serde_yaml::Error> {
+        deserialize_commands(serde_yaml::Deserializer::from_str(yaml))
+    }
+
+    #[test]
+    fn commands_without_action_run_a_program() {
+        let commands = commands(
+            "- program: gpg
+  args: [--decrypt, secret.gpg]
+  working_dir: ''
+",
+        )
+        .unwrap();
+        assert!(matches!(
+            &commands[0],
+            LayerCommands::Exec { program, secret: SecretDelivery::None, .. } if program == "gpg"
+        ));
+    }
+
+    #[test]
+    fn secret_stdin_becomes_a_secret_delivery() {
+        let commands = commands(
+            "- program: gpg
+  args: []
+  working_dir: ''
+  secret_stdin: true
+- program: tar
+  args: []
+  working_dir: ''
+  secret_stdin: false
+- program: age
+  args: []
+  working_dir: ''
+  secret_stdin: true
+  secret:
+    mode: env
+    name: AGE_PASSPHRASE
+",
+        )
+        .unwrap();
+        let deliveries: Vec<&SecretDelivery> = commands
+            .iter()
+            .map(|command| match command {
+                LayerCommands::Exec { secret, .. } => secret,
+                _ => panic!("Command isn't exec"),
+            })
+            .collect();
+        assert_eq!(
+            deliveries,
+            vec![
+                &SecretDelivery::Stdin,
+                &SecretDelivery::None,
+                &SecretDelivery::Env {
+                    name: "AGE_PASSPHRASE".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_with_unknown_placeholders_are_rejected() {
+        assert!(commands(
+            "- action: secure_delete
+  paths: ['{home}/file']
+",
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn secret_file_needs_a_file_or_pipe() {
+        assert!(commands(
+            "- program: age
+  args: [--decrypt, --passphrase-file, '{secret_file}']
+  working_dir: ''
+  secret:
+    mode: file
+- program: cat
+  args: ['{secret_file}']
+  working_dir: ''
+  secret:
+    mode: pipe
+",
+        )
+        .is_ok());
+        assert!(commands(
+            "- program: gpg
+  args: ['{secret_file}']
+  working_dir: ''
+  secret:
+    mode: stdin
+",
+        )
+        .is_err());
+        assert!(commands(
+            "- action: secure_delete
+  paths: ['{secret_file}']
+",
+        )
+        .is_err());
+    }
+
+    fn unlocks(yaml: &str) -> Result<Vec<LayerUnlock>, serde_yaml::Error> {
+        serde_yaml::from_str(yaml)
+    }
+
+    #[test]
+    fn share_files_leaving_the_layer_directory_are_rejected() {
+        assert!(unlocks("- { layer: 7f1c, share_file: out/share }").is_ok());
+        assert!(unlocks("- { layer: 7f1c, share_file: ../share }").is_err());
+        assert!(unlocks("- { layer: 7f1c, share_file: out/../../share }").is_err());
+        assert!(unlocks("- { layer: 7f1c, share_file: /etc/passwd }").is_err());
+        assert!(unlocks("- { layer: 7f1c, share_file: '' }").is_err());
+    }
+}
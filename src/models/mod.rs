@@ -1,2 +1,13 @@
+// shared by the binaries, each of them uses only some of the models
+#![allow(dead_code)]
+
+pub(crate) mod admin;
+pub(crate) mod audit;
+pub(crate) mod delivery;
 pub(crate) mod dms;
+pub(crate) mod estate;
+pub(crate) mod event;
+pub(crate) mod health;
+pub(crate) mod job;
 pub(crate) mod layer;
+pub(crate) mod server;
@@ -0,0 +1,51 @@
+use std::fmt::Write;
+
+/// Writes metrics in the Prometheus text exposition format.
+pub(crate) struct MetricsWriter {
+    output: String,
+}
+
+impl MetricsWriter {
+    pub(crate) fn new() -> MetricsWriter {
+        MetricsWriter {
+            output: String::new(),
+        }
+    }
+
+    /// Starts a metric family, its samples have to follow directly.
+    pub(crate) fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+    }
+
+    pub(crate) fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.output.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label_value(value)))
+                .collect();
+            let _ = write!(self.output, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.output, " {}", format_value(value));
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.output
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
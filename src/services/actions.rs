@@ -0,0 +1,289 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+
+use pgp::composed::{Deserializable, Message};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::models::layer::LayerCommands;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const ARMOR_HEADER: &[u8] = b"-----BEGIN PGP MESSAGE-----";
+
+/// Runs a built-in action in the directory of a layer and returns a summary of what it did.
+pub(crate) fn run_builtin_action(
+    action: &LayerCommands,
+    layer_dir: &Path,
+    secret: &str,
+) -> Result<String, Box<dyn Error>> {
+    match action {
+        LayerCommands::Exec { .. } => Err("exec isn't a built-in action".into()),
+        LayerCommands::DecryptOpenpgp { input, output } => {
+            let input = layer_file(layer_dir, input)?;
+            let output = layer_file(layer_dir, output)?;
+            let written = decrypt_openpgp(File::open(&input)?, File::create(&output)?, secret)?;
+            Ok(format!(
+                "Decrypted {} to {} ({} bytes)",
+                input.display(),
+                output.display(),
+                written
+            ))
+        }
+        LayerCommands::DecryptAge { input, output } => {
+            let input = layer_file(layer_dir, input)?;
+            let output = layer_file(layer_dir, output)?;
+            let written = decrypt_age(File::open(&input)?, File::create(&output)?, secret)?;
+            Ok(format!(
+                "Decrypted {} to {} ({} bytes)",
+                input.display(),
+                output.display(),
+                written
+            ))
+        }
+        LayerCommands::Extract { input, destination } => {
+            let input = layer_file(layer_dir, input)?;
+            let destination = layer_file(layer_dir, destination)?;
+            fs::create_dir_all(&destination)?;
+            let mut reader = BufReader::new(File::open(&input)?);
+            if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+                tar::Archive::new(zstd::stream::read::Decoder::with_buffer(reader)?)
+                    .unpack(&destination)?;
+            } else {
+                tar::Archive::new(reader).unpack(&destination)?;
+            }
+            Ok(format!(
+                "Extracted {} to {}",
+                input.display(),
+                destination.display()
+            ))
+        }
+        LayerCommands::SecureDelete { paths } => {
+            let mut deleted = Vec::new();
+            for path in paths.iter() {
+                let path = layer_file(layer_dir, path)?;
+                secure_delete(&path)?;
+                deleted.push(path.display().to_string());
+            }
+            Ok(format!("Deleted {}", deleted.join(", ")))
+        }
+        LayerCommands::VerifyChecksum { path, sha256 } => {
+            let path = layer_file(layer_dir, path)?;
+            let mut hasher = Sha256::new();
+            io::copy(&mut File::open(&path)?, &mut hasher)?;
+            let actual: String = hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            if !actual.eq_ignore_ascii_case(sha256) {
+                return Err(format!(
+                    "Checksum of {} is {}, expected {}",
+                    path.display(),
+                    actual,
+                    sha256
+                )
+                .into());
+            }
+            Ok(format!("Checksum of {} matches", path.display()))
+        }
+    }
+}
+
+// Built-in actions run in the server process, so their paths must stay in the layer directory,
+// also through symlinks
+pub(crate) fn layer_file(layer_dir: &Path, path: &str) -> Result<PathBuf, String> {
+    // absolute paths, f.e. from placeholders, must be inside the layer directory as well
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(format!("Path \"{}\" must not contain ..", path));
+    }
+    let layer_dir = layer_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", layer_dir.display(), e))?;
+    let joined = layer_dir.join(relative);
+
+    // the closest existing ancestor decides where the path ends up
+    let mut ancestor = joined.as_path();
+    while ancestor.symlink_metadata().is_err() {
+        ancestor = match ancestor.parent() {
+            Some(parent) => parent,
+            None => break,
+        };
+    }
+    let resolved = ancestor
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", ancestor.display(), e))?;
+    if !resolved.starts_with(&layer_dir) {
+        return Err(format!(
+            "Path \"{}\" leads outside of the layer directory",
+            path
+        ));
+    }
+    Ok(joined)
+}
+
+// The packets are parsed as they are read instead of loading the file up front, pgp 0.10 can't
+// decrypt a stream, so the packet being decrypted and its content are still held in memory
+fn decrypt_openpgp<R: Read + Seek, W: Write>(
+    reader: R,
+    mut writer: W,
+    secret: &str,
+) -> Result<u64, Box<dyn Error>> {
+    let mut reader = BufReader::new(reader);
+    let message = if reader.fill_buf()?.starts_with(ARMOR_HEADER) {
+        Message::from_armor_single(reader)?.0
+    } else {
+        Message::from_bytes(reader)?
+    };
+    let passphrase = secret.to_string();
+    let decrypted = message
+        .decrypt_with_password(|| passphrase)?
+        .next()
+        .ok_or("OpenPGP message contains no encrypted data")??;
+    let content = decrypted
+        .decompress()?
+        .get_content()?
+        .ok_or("OpenPGP message contains no literal data")?;
+    writer.write_all(&content)?;
+    writer.flush()?;
+    Ok(content.len() as u64)
+}
+
+fn decrypt_age<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    secret: &str,
+) -> Result<u64, Box<dyn Error>> {
+    let decryptor = match age::Decryptor::new(age::armor::ArmoredReader::new(reader))? {
+        age::Decryptor::Passphrase(decryptor) => decryptor,
+        _ => return Err("age file is encrypted to recipients instead of a passphrase".into()),
+    };
+    let mut reader = decryptor.decrypt(&age::secrecy::Secret::new(secret.to_string()), None)?;
+    let written = io::copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+    Ok(written)
+}
+
+// Overwriting doesn't reach copies kept by journaling filesystems or the wear leveling of SSDs
+pub(crate) fn secure_delete(path: &Path) -> io::Result<()> {
+    let metadata = path.symlink_metadata()?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            secure_delete(&entry?.path())?;
+        }
+        return fs::remove_dir(path);
+    }
+    if metadata.is_file() {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut remaining = metadata.len();
+        while remaining > 0 {
+            let length = remaining.min(buffer.len() as u64) as usize;
+            rand::thread_rng().fill_bytes(&mut buffer[..length]);
+            file.write_all(&buffer[..length])?;
+            remaining -= length as u64;
+        }
+        file.sync_all()?;
+    }
+    // symlinks are removed without touching their target
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // removed again when the test ends
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> TestDir {
+            let dir = std::env::temp_dir().join(format!(
+                "perimetr-actions-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("layer/sub")).unwrap();
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn layer_file_accepts_paths_inside_the_layer_directory() {
+        let dir = TestDir::new("inside");
+        let layer_dir = dir.0.join("layer");
+        let resolved = layer_dir.canonicalize().unwrap();
+        assert_eq!(
+            layer_file(&layer_dir, "sub/out.tar").unwrap(),
+            resolved.join("sub/out.tar")
+        );
+        assert_eq!(
+            layer_file(&layer_dir, "new/dir/file").unwrap(),
+            resolved.join("new/dir/file")
+        );
+        let absolute = resolved.join("sub").display().to_string();
+        assert_eq!(
+            layer_file(&layer_dir, &absolute).unwrap(),
+            resolved.join("sub")
+        );
+    }
+
+    #[test]
+    fn layer_file_rejects_paths_leaving_the_layer_directory() {
+        let dir = TestDir::new("outside");
+        let layer_dir = dir.0.join("layer");
+        assert!(layer_file(&layer_dir, "../secret").is_err());
+        assert!(layer_file(&layer_dir, "sub/../../secret").is_err());
+        assert!(layer_file(&layer_dir, "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn layer_file_rejects_symlinks_leaving_the_layer_directory() {
+        let dir = TestDir::new("symlink");
+        let layer_dir = dir.0.join("layer");
+        std::os::unix::fs::symlink(&dir.0, layer_dir.join("escape")).unwrap();
+        std::os::unix::fs::symlink(layer_dir.join("sub"), layer_dir.join("inner")).unwrap();
+        assert!(layer_file(&layer_dir, "escape/file").is_err());
+        assert!(layer_file(&layer_dir, "escape").is_err());
+        assert!(layer_file(&layer_dir, "inner/file").is_ok());
+    }
+
+    #[test]
+    fn decrypt_openpgp_writes_the_content_of_armored_and_binary_messages() {
+        use pgp::crypto::sym::SymmetricKeyAlgorithm;
+        use pgp::ser::Serialize;
+        use pgp::types::StringToKey;
+        use std::io::Cursor;
+
+        let mut rng = rand::thread_rng();
+        let s2k = StringToKey::new_default(&mut rng);
+        let message = Message::new_literal_bytes("letter.txt", b"last words")
+            .encrypt_with_password(&mut rng, s2k, SymmetricKeyAlgorithm::AES128, || {
+                "passphrase".to_string()
+            })
+            .unwrap();
+
+        for encrypted in [
+            message.to_armored_bytes(None).unwrap(),
+            message.to_bytes().unwrap(),
+        ] {
+            let mut decrypted = Vec::new();
+            let written =
+                decrypt_openpgp(Cursor::new(encrypted), &mut decrypted, "passphrase").unwrap();
+            assert_eq!(written, 10);
+            assert_eq!(decrypted, b"last words");
+        }
+    }
+}
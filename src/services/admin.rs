@@ -0,0 +1,455 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use actix_web::{delete, get, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::IntoParams;
+
+use crate::database::Storage;
+use crate::models::admin::{AdminResult, RescanError, RescanReport};
+use crate::models::event::LayerEvent;
+use crate::models::layer::{Layer, LayerState};
+use crate::services::audit::{record, ACTOR_ADMIN};
+use crate::services::auth::is_admin;
+use crate::services::error::{ApiError, ErrorCode};
+use crate::services::events::EventBroadcaster;
+use crate::services::layer::{
+    ensure_layer_is_idle, find_layer_file, read_layer_status, rec_scan_layer_files,
+    record_state_change, start_decryption,
+};
+use crate::services::shutdown::Decryptions;
+use crate::Configuration;
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct PurgeQuery {
+    /// ID of a single share to delete, all shares of the layer if unset
+    id: Option<i32>,
+}
+
+/// The admin API is only served on its socket, the admin token is required if configured.
+fn authorize(req: &HttpRequest, config: &Configuration) -> Result<(), ApiError> {
+    if config.admin_token.is_some() && !is_admin(req, config) {
+        return Err(ApiError::unauthorized());
+    }
+    Ok(())
+}
+
+fn read_layer(config: &Configuration, layer_uuid: &str) -> Result<(PathBuf, Layer), ApiError> {
+    find_layer_file(
+        config.layer_path.clone(),
+        config.layer_suffix.clone(),
+        layer_uuid.to_string(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(layer_uuid))
+}
+
+/// Writes the new state of a layer, publishes and records it.
+async fn change_state(
+    storage: &dyn Storage,
+    config: &Configuration,
+    broadcaster: &EventBroadcaster,
+    filepath: &PathBuf,
+    layer: &mut Layer,
+    state: LayerState,
+) -> Result<(), ApiError> {
+    layer.state = state;
+    layer.write_metadata(filepath)?;
+    broadcaster.publish(LayerEvent::StateChanged {
+        uuid: layer.uuid.clone(),
+        state: layer.state,
+    });
+    record_state_change(storage, config, layer).await;
+    Ok(())
+}
+
+/// List all layers with their status
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Status of all layers", body = [LayerStatus]),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[get("/layers")]
+pub(crate) async fn list_layers(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+
+    let mut layers = Vec::new();
+    let mut errors = Vec::new();
+    rec_scan_layer_files(
+        &config.layer_path,
+        &config.layer_suffix,
+        &mut layers,
+        &mut errors,
+    )?;
+
+    let mut statuses = Vec::with_capacity(layers.len());
+    for (_, layer) in layers.iter() {
+        statuses.push(read_layer_status(storage.get_ref(), &config.estate.id, layer).await?);
+    }
+
+    Ok(HttpResponse::Ok().json(statuses))
+}
+
+/// Reset the state of a layer to idle
+///
+/// Unlocks layers stuck in decrypting after a crash, or makes decrypted and retired layers accept shares again.
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Layer has been reset", body = AdminResult),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "Layer not found", body = ApiError),
+        (status = 409, description = "Decryption of the layer is still running", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[post("/layer/{uuid}/reset")]
+pub(crate) async fn reset_layer(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+
+    let (filepath, mut layer) = read_layer(&config, &layer_uuid)?;
+    // only layers stuck after a crash may be reset while decrypting
+    if decryptions.is_decrypting(&filepath) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::LayerDecrypting,
+            "Decryption of the layer is still running",
+        ));
+    }
+    let previous_state = layer.state;
+
+    change_state(
+        storage.get_ref(),
+        &config,
+        &broadcaster,
+        &filepath,
+        &mut layer,
+        LayerState::Idle,
+    )
+    .await?;
+    record(
+        storage.get_ref(),
+        &config,
+        "layer_reset",
+        Some(&layer.uuid),
+        ACTOR_ADMIN,
+        json!({ "previous_state": previous_state }),
+    )
+    .await;
+    log::info!("Layer {} has been reset by an administrator", layer.uuid);
+
+    Ok(HttpResponse::Ok().json(AdminResult {
+        message: "Layer has been reset.".to_string(),
+        layer: Some(read_layer_status(storage.get_ref(), &config.estate.id, &layer).await?),
+    }))
+}
+
+/// Retire a layer, it refuses shares until it is reset
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Layer has been retired", body = AdminResult),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "Layer not found", body = ApiError),
+        (status = 409, description = "Layer is being decrypted", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[post("/layer/{uuid}/retire")]
+pub(crate) async fn retire_layer(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+
+    let (filepath, mut layer) = read_layer(&config, &layer_uuid)?;
+    if layer.state == LayerState::Decrypting {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::LayerDecrypting,
+            "Layer is currently being decrypted",
+        ));
+    }
+    let previous_state = layer.state;
+
+    change_state(
+        storage.get_ref(),
+        &config,
+        &broadcaster,
+        &filepath,
+        &mut layer,
+        LayerState::Retired,
+    )
+    .await?;
+    record(
+        storage.get_ref(),
+        &config,
+        "layer_retired",
+        Some(&layer.uuid),
+        ACTOR_ADMIN,
+        json!({ "previous_state": previous_state }),
+    )
+    .await;
+    log::info!("Layer {} has been retired by an administrator", layer.uuid);
+
+    Ok(HttpResponse::Ok().json(AdminResult {
+        message: "Layer has been retired.".to_string(),
+        layer: Some(read_layer_status(storage.get_ref(), &config.estate.id, &layer).await?),
+    }))
+}
+
+/// Run the decryption of a layer again, f.e. after a failed decryption has been fixed
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 202, description = "Decryption started", body = AdminResult),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "Layer not found", body = ApiError),
+        (status = 409, description = "Layer is being decrypted or lacks shares", body = ApiError),
+        (status = 410, description = "Layer has been decrypted or retired, reset it first", body = ApiError),
+        (status = 503, description = "Server is shutting down", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[post("/layer/{uuid}/decrypt")]
+pub(crate) async fn decrypt_layer_again(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+    if decryptions.is_draining() {
+        return Err(ApiError::shutting_down());
+    }
+
+    let (filepath, layer) = read_layer(&config, &layer_uuid)?;
+    ensure_layer_is_idle(&layer)?;
+
+    let mut status = read_layer_status(storage.get_ref(), &config.estate.id, &layer).await?;
+    if status.shares_received < status.shares_required as i64 {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::NotEnoughShares,
+            format!(
+                "Layer has {} of {} required share(s)",
+                status.shares_received, status.shares_required
+            ),
+        ));
+    }
+
+    record(
+        storage.get_ref(),
+        &config,
+        "decryption_requested",
+        Some(&layer.uuid),
+        ACTOR_ADMIN,
+        json!({}),
+    )
+    .await;
+    log::info!(
+        "Decryption of layer {} requested by an administrator",
+        layer.uuid
+    );
+
+    start_decryption(
+        storage.clone(),
+        config.clone(),
+        broadcaster.clone(),
+        decryptions.clone(),
+        filepath,
+        layer,
+    );
+
+    status.state = LayerState::Decrypting;
+    Ok(HttpResponse::Accepted().json(AdminResult {
+        message: "Decrypting.".to_string(),
+        layer: Some(status),
+    }))
+}
+
+/// List the shares stored for a layer
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Shares, identified by their hash", body = [ShareRecord]),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[get("/layer/{uuid}/shares")]
+pub(crate) async fn list_shares(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+
+    let shares = storage
+        .select_share_records(&config.estate.id, layer_uuid.to_string())
+        .await?;
+    record(
+        storage.get_ref(),
+        &config,
+        "shares_listed",
+        Some(&layer_uuid),
+        ACTOR_ADMIN,
+        json!({ "shares": shares.len() }),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(shares))
+}
+
+/// Delete one or all shares of a layer, f.e. a test share that landed in production
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID"), PurgeQuery),
+    responses(
+        (status = 200, description = "Shares have been deleted", body = AdminResult),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "Layer not found", body = ApiError),
+        (status = 409, description = "Layer is being decrypted", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[delete("/layer/{uuid}/shares")]
+pub(crate) async fn purge_shares(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    layer_uuid: web::Path<String>,
+    query: web::Query<PurgeQuery>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+
+    let (_, layer) = read_layer(&config, &layer_uuid)?;
+    if layer.state == LayerState::Decrypting {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::LayerDecrypting,
+            "Layer is currently being decrypted",
+        ));
+    }
+
+    let deleted = storage
+        .delete_shares(&config.estate.id, layer.uuid.clone(), query.id)
+        .await?;
+    record(
+        storage.get_ref(),
+        &config,
+        "shares_purged",
+        Some(&layer.uuid),
+        ACTOR_ADMIN,
+        json!({ "share_id": query.id, "deleted": deleted }),
+    )
+    .await;
+    log::info!(
+        "{} share(s) of layer {} deleted by an administrator",
+        deleted,
+        layer.uuid
+    );
+
+    let status = read_layer_status(storage.get_ref(), &config.estate.id, &layer).await?;
+    broadcaster.publish(LayerEvent::ShareReceived {
+        uuid: status.uuid.clone(),
+        shares_received: status.shares_received,
+        shares_required: status.shares_required,
+    });
+
+    Ok(HttpResponse::Ok().json(AdminResult {
+        message: format!("{} share(s) deleted.", deleted),
+        layer: Some(status),
+    }))
+}
+
+/// Re-scan the layer path
+///
+/// Reports unreadable layer files and duplicate UUIDs, and sends a fresh snapshot to subscribers of the event stream.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Layers found in the layer path", body = RescanReport),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[post("/rescan")]
+pub(crate) async fn rescan_layers(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+) -> Result<HttpResponse, ApiError> {
+    authorize(&req, &config)?;
+
+    let mut layers = Vec::new();
+    let mut errors = Vec::new();
+    rec_scan_layer_files(
+        &config.layer_path,
+        &config.layer_suffix,
+        &mut layers,
+        &mut errors,
+    )?;
+
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut statuses = Vec::with_capacity(layers.len());
+    for (_, layer) in layers.iter() {
+        if !seen.insert(layer.uuid.clone()) {
+            duplicates.push(layer.uuid.clone());
+            continue;
+        }
+        statuses.push(read_layer_status(storage.get_ref(), &config.estate.id, layer).await?);
+    }
+
+    broadcaster.publish(LayerEvent::Snapshot {
+        layers: statuses.clone(),
+    });
+
+    let errors: Vec<RescanError> = errors
+        .into_iter()
+        .map(|(file, error)| RescanError {
+            file: file.display().to_string(),
+            error,
+        })
+        .collect();
+    record(
+        storage.get_ref(),
+        &config,
+        "layers_rescanned",
+        None,
+        ACTOR_ADMIN,
+        json!({
+            "layers": statuses.len(),
+            "errors": errors.len(),
+            "duplicates": duplicates,
+        }),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(RescanReport {
+        layers: statuses,
+        errors,
+        duplicates,
+    }))
+}
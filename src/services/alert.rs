@@ -0,0 +1,46 @@
+use std::error::Error;
+
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::Configuration;
+
+/// Emails an alert to the configured recipients in the background, alerts are logged in any case.
+pub(crate) fn send_alert(config: &Configuration, subject: String, text: String) {
+    log::warn!("Alert: {}", subject);
+
+    if config.alert_email.is_empty() {
+        return;
+    }
+
+    let config = config.clone();
+    std::thread::spawn(move || {
+        for to in config.alert_email.iter() {
+            if let Err(e) = send_alert_email(&config, to, &subject, &text) {
+                log::error!("Failed to send alert to {}: {}", to, e);
+            }
+        }
+    });
+}
+
+fn send_alert_email(
+    config: &Configuration,
+    to: &str,
+    subject: &str,
+    text: &str,
+) -> Result<(), Box<dyn Error>> {
+    let smtp_url = config.smtp_url.as_ref().ok_or("No SMTP relay configured")?;
+    let smtp_from = config
+        .smtp_from
+        .as_ref()
+        .ok_or("No SMTP sender address configured")?;
+
+    let email = Message::builder()
+        .from(smtp_from.parse()?)
+        .to(to.parse()?)
+        .subject(format!("[perimetr] {}", subject))
+        .body(text.to_string())?;
+
+    SmtpTransport::from_url(smtp_url)?.build().send(&email)?;
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use ed25519_dalek::SigningKey;
+
+use crate::database::Storage;
+use crate::models::audit::AuditExport;
+use crate::services::auth::is_admin;
+use crate::services::error::ApiError;
+use crate::Configuration;
+
+// actors that aren't clients
+pub(crate) const ACTOR_ADMIN: &str = "admin";
+pub(crate) const ACTOR_SERVER: &str = "server";
+
+/// Appends an event to the audit log. Failures are logged, they never abort the audited action.
+pub(crate) async fn record(
+    storage: &dyn Storage,
+    config: &Configuration,
+    event: &str,
+    layer_uuid: Option<&str>,
+    actor: &str,
+    details: serde_json::Value,
+) {
+    if let Err(e) = storage
+        .insert_audit_entry(
+            &config.estate.id,
+            config.audit_signing_key.as_ref(),
+            event,
+            layer_uuid.map(|uuid| uuid.to_string()),
+            actor,
+            details.to_string(),
+        )
+        .await
+    {
+        log::error!("Failed to record {} in the audit log: {}", event, e);
+    }
+}
+
+/// Export the audit log
+///
+/// Verify the export with `perimetr audit verify`.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "All entries of the audit log, oldest first", body = AuditExport),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[get("/audit")]
+pub(crate) async fn get_audit_log(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+) -> Result<HttpResponse, ApiError> {
+    if !is_admin(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+
+    let entries = storage.select_audit_entries(&config.estate.id).await?;
+
+    record(
+        storage.get_ref(),
+        &config,
+        "audit_exported",
+        None,
+        ACTOR_ADMIN,
+        serde_json::json!({ "entries": entries.len() }),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(AuditExport {
+        public_key: config
+            .audit_signing_key
+            .as_ref()
+            .map(|key: &SigningKey| base64::encode(key.verifying_key().to_bytes())),
+        entries,
+    }))
+}
@@ -0,0 +1,24 @@
+use actix_web::{http::header, HttpRequest};
+
+use crate::helper::hash::constant_time_eq;
+use crate::Configuration;
+
+/// Checks the `Authorization: Bearer <token>` header against the configured admin token.
+/// Always fails if no admin token is configured.
+pub(crate) fn is_admin(req: &HttpRequest, config: &Configuration) -> bool {
+    let admin_token = match config.admin_token.as_ref() {
+        Some(admin_token) if !admin_token.is_empty() => admin_token,
+        _ => return false,
+    };
+
+    let provided_token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided_token {
+        Some(provided_token) => constant_time_eq(provided_token.as_bytes(), admin_token.as_bytes()),
+        None => false,
+    }
+}
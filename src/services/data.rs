@@ -0,0 +1,490 @@
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+use actix_files::NamedFile;
+use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::database::Storage;
+use crate::helper::hash::sha256_hex;
+use crate::models::layer::{Layer, LayerState, LayerVisibility};
+use crate::services::audit::{record, ACTOR_ADMIN};
+use crate::services::auth::is_admin;
+use crate::services::error::{ApiError, ErrorCode};
+use crate::services::layer::{find_layer_file, SCRATCH_PREFIX};
+use crate::Configuration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header with the download token of a beneficiary, keeps the token out of URLs and access logs
+pub(crate) const DOWNLOAD_TOKEN_HEADER: &str = "X-Download-Token";
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct DownloadQuery {
+    /// Download token of a beneficiary, deprecated in favor of the X-Download-Token header
+    token: Option<String>,
+    /// Expiry of a signed link as UNIX timestamp
+    expires: Option<i64>,
+    /// Signature of a signed link
+    signature: Option<String>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub(crate) struct DownloadLinkRequest {
+    /// Path of the file, relative to the directory of the layer file
+    path: String,
+    /// Validity of the link in seconds
+    expires_in: i64,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub(crate) struct DownloadLink {
+    url: String,
+    expires_at: DateTime<Utc>,
+}
+
+// Who is accessing a file, for the access log
+#[derive(Debug)]
+enum Principal {
+    Anonymous,
+    Beneficiary(String),
+    SignedLink,
+}
+
+impl Principal {
+    fn describe(&self) -> String {
+        match self {
+            Principal::Anonymous => "anonymous".to_string(),
+            Principal::Beneficiary(name) => format!("beneficiary {}", name),
+            Principal::SignedLink => "signed link".to_string(),
+        }
+    }
+}
+
+/// Download a file of a layer
+///
+/// Access is granted by the visibility rules of the layer, a download token of a beneficiary
+/// or a signed link created by an administrator. Layer files are never served.
+#[utoipa::path(
+    get,
+    path = "/data/{uuid}/{path}",
+    params(
+        ("uuid", description = "Layer UUID"),
+        ("path", description = "Path of the file, relative to the directory of the layer file"),
+        ("X-Download-Token" = Option<String>, Header, description = "Download token of a beneficiary"),
+        DownloadQuery,
+    ),
+    responses(
+        (status = 200, description = "File content"),
+        (status = 403, description = "Access denied", body = ApiError),
+        (status = 404, description = "Layer or file not found", body = ApiError),
+    )
+)]
+#[get("/data/{uuid}/{path:.*}")]
+pub(crate) async fn download_file(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    params: web::Path<(String, String)>,
+    query: web::Query<DownloadQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (layer_uuid, path) = params.into_inner();
+    let (filepath, layer) = find_layer_file(
+        config.layer_path.clone(),
+        config.layer_suffix.clone(),
+        layer_uuid.clone(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(&layer_uuid))?;
+
+    let peer = req
+        .peer_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let token = download_token(&req, &query);
+    let principal = match authorize(&config, &layer, &path, &query, token) {
+        Ok(principal) => principal,
+        Err(e) => {
+            log::warn!(
+                "Denied download of \"{}\" from layer {} to {}: {}",
+                path,
+                layer.uuid,
+                peer,
+                e.message
+            );
+            record(
+                storage.get_ref(),
+                &config,
+                "download_denied",
+                Some(&layer.uuid),
+                &peer,
+                json!({ "path": path, "reason": e.message }),
+            )
+            .await;
+            return Err(e);
+        }
+    };
+
+    let file = resolve_layer_file(&config.layer_suffix, &filepath, &path)?;
+
+    log::info!(
+        "Download of \"{}\" from layer {} by {:?} ({})",
+        path,
+        layer.uuid,
+        principal,
+        peer
+    );
+    record(
+        storage.get_ref(),
+        &config,
+        "file_downloaded",
+        Some(&layer.uuid),
+        &peer,
+        json!({ "path": path, "principal": principal.describe() }),
+    )
+    .await;
+
+    Ok(NamedFile::open(file)?.into_response(&req))
+}
+
+/// List the files of a layer the requester may download
+#[utoipa::path(
+    params(
+        ("uuid", description = "Layer UUID"),
+        ("X-Download-Token" = Option<String>, Header, description = "Download token of a beneficiary"),
+        DownloadQuery,
+    ),
+    responses(
+        (status = 200, description = "Paths relative to the directory of the layer file", body = [String]),
+        (status = 404, description = "Layer not found", body = ApiError),
+    )
+)]
+#[get("/layer/{uuid}/files")]
+pub(crate) async fn list_files(
+    req: HttpRequest,
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+    query: web::Query<DownloadQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (filepath, layer) = find_layer_file(
+        config.layer_path.clone(),
+        config.layer_suffix.clone(),
+        layer_uuid.to_string(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(&layer_uuid))?;
+
+    let layer_dir = filepath.parent().unwrap_or(".".as_ref());
+    let token = download_token(&req, &query);
+    let mut files = Vec::new();
+    for file in rec_list_files(layer_dir, layer_dir, &config.layer_suffix)? {
+        // signed links grant access to a single file, they can't be used for listings
+        if query.signature.is_none() && authorize(&config, &layer, &file, &query, token).is_ok() {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    Ok(HttpResponse::Ok().json(files))
+}
+
+/// Create a signed, expiring download link for a file of a layer
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    request_body = DownloadLinkRequest,
+    responses(
+        (status = 200, description = "Signed download link", body = DownloadLink),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+        (status = 404, description = "Layer or file not found", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[post("/layer/{uuid}/links")]
+pub(crate) async fn create_download_link(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+    link_request: web::Json<DownloadLinkRequest>,
+) -> Result<HttpResponse, ApiError> {
+    if !is_admin(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+
+    let (filepath, layer) = find_layer_file(
+        config.layer_path.clone(),
+        config.layer_suffix.clone(),
+        layer_uuid.to_string(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(&layer_uuid))?;
+
+    resolve_layer_file(&config.layer_suffix, &filepath, &link_request.path)?;
+
+    let expires_at = Utc::now() + chrono::Duration::seconds(link_request.expires_in.max(1));
+    let expires = expires_at.timestamp();
+    let signature = sign_link(
+        &config.link_secret,
+        &layer.uuid,
+        &link_request.path,
+        expires,
+    );
+
+    log::info!(
+        "Created download link for \"{}\" of layer {}, valid until {}",
+        link_request.path,
+        layer.uuid,
+        expires_at
+    );
+    record(
+        storage.get_ref(),
+        &config,
+        "download_link_created",
+        Some(&layer.uuid),
+        ACTOR_ADMIN,
+        json!({ "path": link_request.path, "expires_at": expires_at }),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(DownloadLink {
+        url: format!(
+            "/data/{}/{}?expires={}&signature={}",
+            layer.uuid, link_request.path, expires, signature
+        ),
+        expires_at,
+    }))
+}
+
+// the header takes precedence over the query string of older links
+fn download_token<'a>(req: &'a HttpRequest, query: &'a DownloadQuery) -> Option<&'a str> {
+    req.headers()
+        .get(DOWNLOAD_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or(query.token.as_deref())
+}
+
+fn authorize(
+    config: &Configuration,
+    layer: &Layer,
+    path: &str,
+    query: &DownloadQuery,
+    token: Option<&str>,
+) -> Result<Principal, ApiError> {
+    if let (Some(expires), Some(signature)) = (query.expires, query.signature.as_ref()) {
+        if expires < Utc::now().timestamp() {
+            return Err(access_denied("Download link has expired"));
+        }
+        if !verify_link(&config.link_secret, &layer.uuid, path, expires, signature) {
+            return Err(access_denied("Invalid download link"));
+        }
+        return Ok(Principal::SignedLink);
+    }
+
+    if let Some(token) = token {
+        let token_hash = sha256_hex(token.as_bytes());
+        let beneficiary = layer
+            .access
+            .tokens
+            .iter()
+            .find(|t| t.sha256.eq_ignore_ascii_case(&token_hash))
+            .ok_or_else(|| access_denied("Invalid download token"))?;
+        if layer.state != LayerState::Decrypted {
+            return Err(access_denied("Layer hasn't been decrypted yet"));
+        }
+        if !path_is_listed(&beneficiary.files, path) {
+            return Err(access_denied("File isn't available for this token"));
+        }
+        return Ok(Principal::Beneficiary(beneficiary.name.clone()));
+    }
+
+    let visible = match layer.access.visibility {
+        LayerVisibility::Public => true,
+        LayerVisibility::Decrypted => layer.state == LayerState::Decrypted,
+        LayerVisibility::Private => false,
+    };
+    if !visible || !path_is_listed(&layer.access.files, path) {
+        return Err(access_denied("File isn't publicly available"));
+    }
+    Ok(Principal::Anonymous)
+}
+
+// An empty list allows every file, entries match files and whole subdirectories
+pub(crate) fn path_is_listed(files: &[String], path: &str) -> bool {
+    files.is_empty()
+        || files.iter().any(|file| {
+            let file = file.trim_end_matches('/');
+            path == file || path.starts_with(&format!("{}/", file))
+        })
+}
+
+/// Resolves a requested path inside the directory of a layer file, refusing anything outside of it,
+/// layer files and the files of nested layers and decryption jobs.
+fn resolve_layer_file(
+    layer_suffix: &str,
+    filepath: &Path,
+    path: &str,
+) -> Result<PathBuf, ApiError> {
+    let not_found = || {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::FileNotFound,
+            format!("File \"{}\" not found", path),
+        )
+    };
+
+    let relative = Path::new(path);
+    if path.is_empty()
+        || path.ends_with(layer_suffix)
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(not_found());
+    }
+
+    let layer_dir = filepath.parent().unwrap_or(".".as_ref()).canonicalize()?;
+    let file = layer_dir
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| not_found())?;
+
+    // symlinks must not lead out of the layer directory
+    if !file.starts_with(&layer_dir) || !file.is_file() {
+        return Err(not_found());
+    }
+    for dir in file.ancestors().skip(1) {
+        if dir == layer_dir {
+            break;
+        }
+        if is_foreign_dir(dir, layer_suffix)? {
+            return Err(not_found());
+        }
+    }
+
+    Ok(file)
+}
+
+/// Lists the files below a layer directory, without layer files and the directories of nested layers and decryption jobs.
+pub(crate) fn rec_list_files(
+    base: &Path,
+    dir: &Path,
+    suffix: &str,
+) -> Result<Vec<String>, io::Error> {
+    let mut files = Vec::new();
+
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        if dir_entry.metadata()?.is_dir() {
+            if !is_foreign_dir(&dir_entry.path(), suffix)? {
+                files.append(&mut rec_list_files(base, &dir_entry.path(), suffix)?);
+            }
+        } else if !dir_entry.file_name().to_string_lossy().ends_with(suffix) {
+            if let Ok(relative) = dir_entry.path().strip_prefix(base) {
+                files.push(relative.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+// scratch directories of decryption jobs and directories of nested layers belong to someone else
+fn is_foreign_dir(dir: &Path, suffix: &str) -> Result<bool, io::Error> {
+    if dir
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(SCRATCH_PREFIX))
+    {
+        return Ok(true);
+    }
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        if dir_entry.file_name().to_string_lossy().ends_with(suffix)
+            && dir_entry.metadata()?.is_file()
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn link_mac(secret: &[u8], layer_uuid: &str, path: &str, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}\n{}\n{}", layer_uuid, path, expires).as_bytes());
+    mac
+}
+
+fn sign_link(secret: &[u8], layer_uuid: &str, path: &str, expires: i64) -> String {
+    let signature = link_mac(secret, layer_uuid, path, expires)
+        .finalize()
+        .into_bytes();
+    base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+}
+
+fn verify_link(secret: &[u8], layer_uuid: &str, path: &str, expires: i64, signature: &str) -> bool {
+    match base64::decode_config(signature, base64::URL_SAFE_NO_PAD) {
+        Ok(signature) => link_mac(secret, layer_uuid, path, expires)
+            .verify_slice(&signature)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn access_denied(message: &str) -> ApiError {
+    ApiError::new(StatusCode::FORBIDDEN, ErrorCode::AccessDenied, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // removed again when the test ends
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> TestDir {
+            let dir =
+                std::env::temp_dir().join(format!("perimetr-data-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+
+        fn write(&self, path: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "content").unwrap();
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn nested_layers_and_scratch_directories_are_not_served() {
+        let dir = TestDir::new("nested");
+        dir.write("outer.layer");
+        dir.write("out/file");
+        dir.write("inner/inner.layer");
+        dir.write("inner/secret");
+        dir.write("inner/sub/secret");
+        dir.write(".scratch-7/intermediate");
+
+        let mut files = rec_list_files(&dir.0, &dir.0, ".layer").unwrap();
+        files.sort();
+        assert_eq!(files, vec!["out/file".to_string()]);
+
+        let filepath = dir.0.join("outer.layer");
+        assert!(resolve_layer_file(".layer", &filepath, "out/file").is_ok());
+        assert!(resolve_layer_file(".layer", &filepath, "outer.layer").is_err());
+        assert!(resolve_layer_file(".layer", &filepath, "inner/secret").is_err());
+        assert!(resolve_layer_file(".layer", &filepath, "inner/sub/secret").is_err());
+        assert!(resolve_layer_file(".layer", &filepath, ".scratch-7/intermediate").is_err());
+    }
+}
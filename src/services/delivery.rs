@@ -0,0 +1,372 @@
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, MultiPart, SinglePart};
+use lettre::{Message, SmtpTransport, Transport};
+use pgp::composed::{Deserializable, SignedPublicKey};
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::ser::Serialize;
+use pgp::types::{CompressionAlgorithm, KeyTrait};
+use rand::rngs::OsRng;
+use serde_json::json;
+
+use crate::database::Storage;
+use crate::models::delivery::DeliveryState;
+use crate::models::event::LayerEvent;
+use crate::models::layer::{Beneficiary, DeliveryMethod, Layer};
+use crate::services::audit::{record, ACTOR_SERVER};
+use crate::services::auth::is_admin;
+use crate::services::data::{path_is_listed, rec_list_files};
+use crate::services::error::ApiError;
+use crate::services::events::EventBroadcaster;
+use crate::Configuration;
+
+/// Delivers the decrypted files of a layer to its beneficiaries and records the outcome of each delivery.
+pub(crate) async fn deliver_to_beneficiaries(
+    storage: &dyn Storage,
+    config: &Configuration,
+    broadcaster: &EventBroadcaster,
+    layer: &Layer,
+    filepath: &Path,
+) {
+    if layer.beneficiaries.is_empty() {
+        return;
+    }
+
+    let layer_dir = filepath.parent().unwrap_or(".".as_ref());
+    // deliveries send mails and encrypt files, which blocks
+    let blocking_config = Arc::new(config.clone());
+    let files = match rec_list_files(layer_dir, layer_dir, &config.layer_suffix) {
+        Ok(files) => files,
+        Err(e) => {
+            log::error!("Failed to list files of layer {}: {}", layer.uuid, e);
+            Vec::new()
+        }
+    };
+
+    let mut failed = 0;
+    for beneficiary in layer.beneficiaries.iter() {
+        let delivery_id = match storage
+            .insert_delivery(
+                &config.estate.id,
+                layer.uuid.clone(),
+                beneficiary.name.clone(),
+                beneficiary.delivery.name().to_string(),
+            )
+            .await
+        {
+            Ok(delivery_id) => delivery_id,
+            Err(e) => {
+                log::error!(
+                    "Failed to record delivery to {} of layer {}: {}",
+                    beneficiary.name,
+                    layer.uuid,
+                    e
+                );
+                failed += 1;
+                continue;
+            }
+        };
+
+        let selected_files = select_files(&files, &beneficiary.files, layer.output_dir.as_deref());
+
+        let result = {
+            let config = Arc::clone(&blocking_config);
+            let layer_uuid = layer.uuid.clone();
+            let layer_dir = layer_dir.to_path_buf();
+            let beneficiary = beneficiary.clone();
+            let files = selected_files.clone();
+            web::block(move || {
+                deliver(&config, &layer_uuid, &layer_dir, &beneficiary, &files)
+                    .map_err(|e| e.to_string())
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result)
+        };
+
+        let (state, error) = match result {
+            Ok(()) => {
+                log::info!(
+                    "Delivered {} file(s) of layer {} to {} by {}",
+                    selected_files.len(),
+                    layer.uuid,
+                    beneficiary.name,
+                    beneficiary.delivery.name()
+                );
+                (DeliveryState::Delivered, None)
+            }
+            Err(e) => {
+                log::error!(
+                    "Delivery of layer {} to {} failed: {}",
+                    layer.uuid,
+                    beneficiary.name,
+                    e
+                );
+                failed += 1;
+                (DeliveryState::Failed, Some(e.to_string()))
+            }
+        };
+
+        if let Err(e) = storage
+            .finish_delivery(delivery_id, state, error.clone())
+            .await
+        {
+            log::error!("Failed to store result of delivery {}: {}", delivery_id, e);
+        }
+        record(
+            storage,
+            config,
+            "delivery_finished",
+            Some(&layer.uuid),
+            ACTOR_SERVER,
+            json!({
+                "beneficiary": beneficiary.name,
+                "method": beneficiary.delivery.name(),
+                "files": selected_files,
+                "state": state.as_str(),
+                "error": error,
+            }),
+        )
+        .await;
+    }
+
+    broadcaster.publish(LayerEvent::DeliveryFinished {
+        uuid: layer.uuid.clone(),
+        beneficiaries: layer.beneficiaries.len(),
+        failed,
+    });
+}
+
+/// List deliveries of a layer to its beneficiaries
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Deliveries, newest first", body = [Delivery]),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[get("/layer/{uuid}/deliveries")]
+pub(crate) async fn get_layer_deliveries(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    if !is_admin(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+
+    let deliveries = storage
+        .select_deliveries(&config.estate.id, layer_uuid.to_string())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+// without a selection a beneficiary receives the outputs of the layer, not its encrypted input
+fn select_files(files: &[String], selection: &[String], output_dir: Option<&str>) -> Vec<String> {
+    let outputs: Vec<String> = output_dir
+        .map(|dir| dir.trim_start_matches("./").trim_end_matches('/'))
+        .filter(|dir| !dir.is_empty() && *dir != ".")
+        .map(str::to_string)
+        .into_iter()
+        .collect();
+    let selection = if selection.is_empty() {
+        &outputs
+    } else {
+        selection
+    };
+    files
+        .iter()
+        .filter(|file| path_is_listed(selection, file))
+        .cloned()
+        .collect()
+}
+
+fn deliver(
+    config: &Configuration,
+    layer_uuid: &str,
+    layer_dir: &Path,
+    beneficiary: &Beneficiary,
+    files: &[String],
+) -> Result<(), Box<dyn Error>> {
+    if files.is_empty() {
+        return Err("No files to deliver".into());
+    }
+
+    match &beneficiary.delivery {
+        DeliveryMethod::Email { to } => {
+            deliver_by_email(config, layer_uuid, layer_dir, beneficiary, to, files)
+        }
+        DeliveryMethod::Directory { path } => {
+            deliver_to_directory(layer_dir, &layer_dir.join(path), files)
+        }
+        DeliveryMethod::Encrypt { public_key, path } => deliver_encrypted(
+            layer_dir,
+            &layer_dir.join(public_key),
+            &layer_dir.join(path),
+            files,
+        ),
+    }
+}
+
+fn deliver_by_email(
+    config: &Configuration,
+    layer_uuid: &str,
+    layer_dir: &Path,
+    beneficiary: &Beneficiary,
+    to: &str,
+    files: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let smtp_url = config.smtp_url.as_ref().ok_or("No SMTP relay configured")?;
+    let smtp_from = config
+        .smtp_from
+        .as_ref()
+        .ok_or("No SMTP sender address configured")?;
+
+    let text = match beneficiary.message.as_ref() {
+        Some(message) => message.clone(),
+        None => format!(
+            "Dear {},\n\nthe perimetr layer {} has been unlocked. The files designated for you are attached.\n",
+            beneficiary.name, layer_uuid
+        ),
+    };
+
+    let mut body = MultiPart::mixed().singlepart(SinglePart::plain(text));
+    for file in files {
+        let filename = Path::new(file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.clone());
+        body = body.singlepart(Attachment::new(filename).body(
+            fs::read(layer_dir.join(file))?,
+            ContentType::parse("application/octet-stream")?,
+        ));
+    }
+
+    let email = Message::builder()
+        .from(smtp_from.parse()?)
+        .to(to.parse()?)
+        .subject(format!("Heritage of perimetr layer {}", layer_uuid))
+        .multipart(body)?;
+
+    SmtpTransport::from_url(smtp_url)?.build().send(&email)?;
+
+    Ok(())
+}
+
+fn deliver_to_directory(
+    layer_dir: &Path,
+    target_dir: &Path,
+    files: &[String],
+) -> Result<(), Box<dyn Error>> {
+    for file in files {
+        let target = target_dir.join(file);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(layer_dir.join(file), &target)?;
+    }
+
+    Ok(())
+}
+
+fn deliver_encrypted(
+    layer_dir: &Path,
+    public_key: &Path,
+    target_dir: &Path,
+    files: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let key = read_public_key(public_key)?;
+    // subkeys marked for encryption, as gpg picks them
+    let subkeys: Vec<_> = key
+        .public_subkeys
+        .iter()
+        .filter(|subkey| subkey.is_encryption_key())
+        .filter(|subkey| {
+            subkey.signatures.iter().any(|signature| {
+                let flags = signature.key_flags();
+                flags.encrypt_comms() || flags.encrypt_storage()
+            })
+        })
+        .collect();
+    if subkeys.is_empty() && !key.primary_key.is_encryption_key() {
+        return Err(format!("{} has no encryption key", public_key.display()).into());
+    }
+
+    for file in files {
+        let target = target_dir.join(format!("{}.gpg", file));
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let filename = Path::new(file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.clone());
+        let message =
+            pgp::Message::new_literal_bytes(filename.as_str(), &fs::read(layer_dir.join(file))?)
+                .compress(CompressionAlgorithm::ZLIB)?;
+        let encrypted = if subkeys.is_empty() {
+            message.encrypt_to_keys(
+                &mut OsRng,
+                SymmetricKeyAlgorithm::AES256,
+                &[&key.primary_key],
+            )?
+        } else {
+            message.encrypt_to_keys(&mut OsRng, SymmetricKeyAlgorithm::AES256, &subkeys)?
+        };
+        fs::write(&target, encrypted.to_bytes()?)?;
+    }
+
+    Ok(())
+}
+
+// armored or binary OpenPGP public key, f.e. from gpg --export
+fn read_public_key(path: &Path) -> Result<SignedPublicKey, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    let key = if bytes.starts_with(b"-----BEGIN PGP PUBLIC KEY BLOCK-----") {
+        SignedPublicKey::from_armor_single(Cursor::new(bytes))?.0
+    } else {
+        SignedPublicKey::from_bytes(Cursor::new(bytes))?
+    };
+    key.verify()?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<String> {
+        vec![
+            "layer.tar.zst.gpg".to_string(),
+            "out/letter.txt".to_string(),
+            "out/photos/1.jpg".to_string(),
+        ]
+    }
+
+    #[test]
+    fn beneficiaries_without_files_receive_the_output_dir() {
+        assert_eq!(
+            select_files(&files(), &[], Some("./out/")),
+            vec!["out/letter.txt".to_string(), "out/photos/1.jpg".to_string()]
+        );
+        assert_eq!(select_files(&files(), &[], None), files());
+    }
+
+    #[test]
+    fn beneficiaries_receive_their_selection() {
+        assert_eq!(
+            select_files(&files(), &["out/photos".to_string()], Some("out")),
+            vec!["out/photos/1.jpg".to_string()]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Stable, machine readable error codes of the API
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ErrorCode {
+    LayerNotFound,
+    MalformedShare,
+    InvalidShare,
+    DuplicateShare,
+    LayerDecrypting,
+    LayerDecrypted,
+    LayerRetired,
+    NotEnoughShares,
+    FileNotFound,
+    AccessDenied,
+    Unauthorized,
+    RateLimited,
+    LockedOut,
+    PayloadTooLarge,
+    ShuttingDown,
+    InternalError,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub(crate) struct ApiError {
+    #[serde(skip)]
+    pub(crate) status: StatusCode,
+    pub(crate) code: ErrorCode,
+    pub(crate) message: String,
+    // seconds, sent as Retry-After header
+    #[serde(skip)]
+    pub(crate) retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, code: ErrorCode, message: impl Into<String>) -> ApiError {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    pub(crate) fn with_retry_after(mut self, seconds: u64) -> ApiError {
+        self.retry_after = Some(seconds);
+        self
+    }
+
+    pub(crate) fn layer_not_found(uuid: &str) -> ApiError {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::LayerNotFound,
+            format!("Layer {} not found", uuid),
+        )
+    }
+
+    pub(crate) fn unauthorized() -> ApiError {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            ErrorCode::Unauthorized,
+            "A valid admin token is required",
+        )
+    }
+
+    pub(crate) fn shutting_down() -> ApiError {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::ShuttingDown,
+            "Server is shutting down, please try again later",
+        )
+        .with_retry_after(60)
+    }
+
+    pub(crate) fn internal() -> ApiError {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::InternalError,
+            "Internal server error",
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        if let Some(retry_after) = self.retry_after {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        response.json(self)
+    }
+}
+
+// Internal errors are logged but not exposed to clients
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> ApiError {
+        log::error!("Database error: {}", e);
+        ApiError::internal()
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> ApiError {
+        log::error!("I/O error: {}", e);
+        ApiError::internal()
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for ApiError {
+    fn from(e: Box<dyn std::error::Error>) -> ApiError {
+        log::error!("Internal error: {}", e);
+        ApiError::internal()
+    }
+}
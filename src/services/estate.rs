@@ -0,0 +1,32 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::models::estate::EstateInfo;
+use crate::Configuration;
+
+/// Configurations of all estates hosted by the server
+pub(crate) struct Estates(pub(crate) Vec<Configuration>);
+
+/// Get name, keepers and branding of the estate
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Estate the share page belongs to", body = EstateInfo),
+    )
+)]
+#[get("/estate")]
+pub(crate) async fn get_estate(config: web::Data<Configuration>) -> HttpResponse {
+    HttpResponse::Ok().json(&config.estate)
+}
+
+/// List estates hosted by the server
+///
+/// The API of an estate is available below `/estates/{id}`.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Estates hosted by the server", body = [EstateInfo]),
+    )
+)]
+#[get("/estates")]
+pub(crate) async fn list_estates(estates: web::Data<Estates>) -> HttpResponse {
+    let estates: Vec<&EstateInfo> = estates.0.iter().map(|config| &config.estate).collect();
+    HttpResponse::Ok().json(estates)
+}
@@ -0,0 +1,92 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use actix_web::{
+    get,
+    http::header::{CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE},
+    web::{self, Bytes},
+    HttpResponse,
+};
+use futures_util::{future, stream, StreamExt};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
+
+use crate::database::Storage;
+use crate::models::event::LayerEvent;
+use crate::services::error::ApiError;
+use crate::services::layer::{read_layer_status, rec_read_layer_files};
+use crate::Configuration;
+
+// Events a slow subscriber may fall behind before it misses some
+const EVENT_BUFFER: usize = 256;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Fans out layer events to all subscribers of the event stream.
+pub(crate) struct EventBroadcaster {
+    sender: broadcast::Sender<LayerEvent>,
+}
+
+impl EventBroadcaster {
+    pub(crate) fn new() -> EventBroadcaster {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        EventBroadcaster { sender }
+    }
+
+    pub(crate) fn publish(&self, event: LayerEvent) {
+        // fails only if nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<LayerEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Subscribe to layer events
+///
+/// Server-Sent Events stream, every event is a JSON object in the `data` field.
+/// The first event is a `snapshot` of all layers.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Stream of layer events", body = LayerEvent, content_type = "text/event-stream"),
+    )
+)]
+#[get("/events")]
+pub(crate) async fn get_events(
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+) -> Result<HttpResponse, ApiError> {
+    // subscribe before taking the snapshot so no change gets lost in between
+    let receiver = broadcaster.subscribe();
+
+    let layers = rec_read_layer_files(config.layer_path.clone(), config.layer_suffix.clone())?;
+    let mut statuses = Vec::with_capacity(layers.len());
+    for layer in layers.iter() {
+        statuses.push(read_layer_status(storage.get_ref(), &config.estate.id, layer).await?);
+    }
+
+    let events = stream::once(future::ready(LayerEvent::Snapshot { layers: statuses }))
+        .chain(BroadcastStream::new(receiver).filter_map(|event| future::ready(event.ok())))
+        .map(|event| format_event(&event));
+    let keep_alive = IntervalStream::new(tokio::time::interval(KEEP_ALIVE_INTERVAL))
+        .map(|_| Bytes::from_static(b": keep-alive\n\n"));
+
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "text/event-stream"))
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        // prevents the compression middleware from buffering events
+        .insert_header((CONTENT_ENCODING, "identity"))
+        .streaming(stream::select(events, keep_alive).map(Ok::<_, Infallible>)))
+}
+
+fn format_event(event: &LayerEvent) -> Bytes {
+    match serde_json::to_string(event) {
+        Ok(data) => Bytes::from(format!("data: {}\n\n", data)),
+        Err(e) => {
+            log::error!("Failed to serialize event: {}", e);
+            Bytes::new()
+        }
+    }
+}
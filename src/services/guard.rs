@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{http::StatusCode, HttpRequest};
+
+use crate::models::server::ShareLimitsConfig;
+use crate::services::error::{ApiError, ErrorCode};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// Tracked addresses and layers before expired entries are dropped
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Rate limits and locks out addresses submitting shares, kept in memory.
+///
+/// Estates are guarded separately, layers of different estates may share a UUID.
+pub(crate) struct ShareGuard {
+    limits: ShareLimitsConfig,
+    state: Mutex<GuardState>,
+}
+
+// keyed by estate and address or layer UUID
+#[derive(Default)]
+struct GuardState {
+    ip_requests: HashMap<(String, IpAddr), Window>,
+    layer_requests: HashMap<(String, String), Window>,
+    invalid_attempts: HashMap<(String, IpAddr), Window>,
+    lockouts: HashMap<(String, IpAddr), Instant>,
+    // by estate since start, for metrics
+    throttled: HashMap<String, Throttled>,
+}
+
+/// Share submissions of an estate refused without verification
+#[derive(Default, Clone, Copy)]
+pub(crate) struct Throttled {
+    pub(crate) rate_limited: u64,
+    pub(crate) locked_out: u64,
+}
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+impl Window {
+    /// Counts a request and returns the seconds until the window resets if the limit is exceeded.
+    fn hit(&mut self, now: Instant, length: Duration, limit: u32) -> Option<u64> {
+        if now.duration_since(self.started) >= length {
+            self.started = now;
+            self.count = 0;
+        }
+        self.count += 1;
+        if self.count > limit {
+            Some(remaining_secs(self.started + length, now))
+        } else {
+            None
+        }
+    }
+}
+
+impl ShareGuard {
+    pub(crate) fn new(limits: ShareLimitsConfig) -> ShareGuard {
+        ShareGuard {
+            limits,
+            state: Mutex::new(GuardState::default()),
+        }
+    }
+
+    /// Submissions to an estate rejected because of rate limits and lockouts since the start of
+    /// the server.
+    pub(crate) fn rejected_submissions(&self, estate: &str) -> Throttled {
+        let state = self.state.lock().unwrap();
+        state.throttled.get(estate).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn max_share_size(&self) -> usize {
+        self.limits.max_share_size
+    }
+
+    /// Address of the client, from X-Forwarded-For only if configured.
+    pub(crate) fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        if self.limits.trust_forwarded_for {
+            if let Some(ip) = req
+                .connection_info()
+                .realip_remote_addr()
+                .and_then(|addr| addr.parse::<IpAddr>().ok())
+            {
+                return Some(ip);
+            }
+        }
+        req.peer_addr().map(|addr| addr.ip())
+    }
+
+    /// Fails if the address is locked out or the address or layer exceeded its rate limit.
+    pub(crate) fn check(
+        &self,
+        estate: &str,
+        ip: Option<IpAddr>,
+        layer_uuid: &str,
+    ) -> Result<(), ApiError> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.prune(now, self.lockout_duration());
+
+        if let Some(ip) = ip {
+            let key = (estate.to_string(), ip);
+            if let Some(until) = state.lockouts.get(&key) {
+                if *until > now {
+                    let retry_after = remaining_secs(*until, now);
+                    state.throttled(estate).locked_out += 1;
+                    return Err(locked_out(retry_after));
+                }
+                state.lockouts.remove(&key);
+            }
+
+            if let Some(retry_after) = state
+                .ip_requests
+                .entry(key)
+                .or_insert_with(|| Window {
+                    started: now,
+                    count: 0,
+                })
+                .hit(now, RATE_WINDOW, self.limits.requests_per_ip)
+            {
+                log::warn!(
+                    "Rate limit of share submissions to estate {} exceeded by {}",
+                    estate,
+                    ip
+                );
+                state.throttled(estate).rate_limited += 1;
+                return Err(rate_limited(retry_after));
+            }
+        }
+
+        if let Some(retry_after) = state
+            .layer_requests
+            .entry((estate.to_string(), layer_uuid.to_string()))
+            .or_insert_with(|| Window {
+                started: now,
+                count: 0,
+            })
+            .hit(now, RATE_WINDOW, self.limits.requests_per_layer)
+        {
+            log::warn!(
+                "Rate limit of share submissions exceeded for layer {} of estate {}",
+                layer_uuid,
+                estate
+            );
+            state.throttled(estate).rate_limited += 1;
+            return Err(rate_limited(retry_after));
+        }
+
+        Ok(())
+    }
+
+    /// Counts an invalid submission to an estate and returns true if it locked the address out.
+    pub(crate) fn record_invalid(&self, estate: &str, ip: Option<IpAddr>) -> bool {
+        let key = match ip {
+            Some(ip) => (estate.to_string(), ip),
+            None => return false,
+        };
+
+        let now = Instant::now();
+        let lockout_duration = self.lockout_duration();
+        let mut state = self.state.lock().unwrap();
+
+        let exceeded = state
+            .invalid_attempts
+            .entry(key.clone())
+            .or_insert_with(|| Window {
+                started: now,
+                count: 0,
+            })
+            .hit(now, lockout_duration, self.limits.max_invalid_attempts)
+            .is_some();
+        if exceeded {
+            state.invalid_attempts.remove(&key);
+            state.lockouts.insert(key, now + lockout_duration);
+        }
+        exceeded
+    }
+
+    fn lockout_duration(&self) -> Duration {
+        Duration::from_secs(self.limits.lockout_duration)
+    }
+}
+
+impl GuardState {
+    fn throttled(&mut self, estate: &str) -> &mut Throttled {
+        self.throttled.entry(estate.to_string()).or_default()
+    }
+
+    fn prune(&mut self, now: Instant, lockout_duration: Duration) {
+        if self.ip_requests.len() + self.layer_requests.len() + self.invalid_attempts.len()
+            < PRUNE_THRESHOLD
+        {
+            return;
+        }
+        self.ip_requests
+            .retain(|_, window| now.duration_since(window.started) < RATE_WINDOW);
+        self.layer_requests
+            .retain(|_, window| now.duration_since(window.started) < RATE_WINDOW);
+        self.invalid_attempts
+            .retain(|_, window| now.duration_since(window.started) < lockout_duration);
+        self.lockouts.retain(|_, until| *until > now);
+    }
+}
+
+fn remaining_secs(until: Instant, now: Instant) -> u64 {
+    // rounded up, a client retrying after 0 seconds would be rejected again
+    until.saturating_duration_since(now).as_secs() + 1
+}
+
+fn rate_limited(retry_after: u64) -> ApiError {
+    ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::RateLimited,
+        "Too many share submissions, please retry later",
+    )
+    .with_retry_after(retry_after)
+}
+
+fn locked_out(retry_after: u64) -> ApiError {
+    ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::LockedOut,
+        "Too many invalid shares from this address, please retry later",
+    )
+    .with_retry_after(retry_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> ShareGuard {
+        ShareGuard::new(ShareLimitsConfig {
+            requests_per_ip: 3,
+            requests_per_layer: 5,
+            max_invalid_attempts: 2,
+            lockout_duration: 600,
+            max_share_size: 1024,
+            trust_forwarded_for: false,
+        })
+    }
+
+    fn ip(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([192, 0, 2, last]))
+    }
+
+    #[test]
+    fn window_resets_after_its_length() {
+        let start = Instant::now();
+        let mut window = Window {
+            started: start,
+            count: 0,
+        };
+        assert_eq!(window.hit(start, RATE_WINDOW, 2), None);
+        assert_eq!(window.hit(start, RATE_WINDOW, 2), None);
+        assert_eq!(window.hit(start, RATE_WINDOW, 2), Some(61));
+        assert_eq!(
+            window.hit(start + Duration::from_secs(30), RATE_WINDOW, 2),
+            Some(31)
+        );
+        assert_eq!(window.hit(start + RATE_WINDOW, RATE_WINDOW, 2), None);
+        assert_eq!(window.count, 1);
+    }
+
+    #[test]
+    fn check_rate_limits_addresses() {
+        let guard = guard();
+        for _ in 0..3 {
+            assert!(guard.check("default", ip(1), "layer").is_ok());
+        }
+        assert!(guard.check("default", ip(1), "layer").is_err());
+        assert!(guard.check("default", ip(2), "layer").is_ok());
+        assert_eq!(guard.rejected_submissions("default").rate_limited, 1);
+    }
+
+    #[test]
+    fn check_rate_limits_layers_across_addresses() {
+        let guard = guard();
+        for last in 0..5 {
+            assert!(guard.check("default", ip(last), "layer").is_ok());
+        }
+        assert!(guard.check("default", ip(5), "layer").is_err());
+        assert!(guard.check("default", ip(5), "other").is_ok());
+    }
+
+    #[test]
+    fn record_invalid_locks_the_address_out() {
+        let guard = guard();
+        assert!(!guard.record_invalid("default", ip(1)));
+        assert!(!guard.record_invalid("default", ip(1)));
+        assert!(guard.record_invalid("default", ip(1)));
+        assert!(guard.check("default", ip(1), "layer").is_err());
+        assert!(guard.check("default", ip(2), "layer").is_ok());
+        assert_eq!(guard.rejected_submissions("default").locked_out, 1);
+        // without an address nothing can be locked out
+        assert!(!guard.record_invalid("default", None));
+    }
+
+    #[test]
+    fn estates_are_guarded_separately() {
+        let guard = guard();
+        for _ in 0..3 {
+            guard.record_invalid("a", ip(1));
+        }
+        assert!(guard.check("a", ip(1), "layer").is_err());
+        assert!(guard.check("b", ip(1), "layer").is_ok());
+
+        // layers of different estates may share a UUID
+        for last in 10..15 {
+            assert!(guard.check("a", ip(last), "shared").is_ok());
+        }
+        assert!(guard.check("a", ip(15), "shared").is_err());
+        assert!(guard.check("b", ip(15), "shared").is_ok());
+
+        assert_eq!(guard.rejected_submissions("a").locked_out, 1);
+        assert_eq!(guard.rejected_submissions("a").rate_limited, 1);
+        assert_eq!(guard.rejected_submissions("b").locked_out, 0);
+        assert_eq!(guard.rejected_submissions("b").rate_limited, 0);
+    }
+}
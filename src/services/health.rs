@@ -0,0 +1,88 @@
+use actix_web::{get, web, HttpResponse};
+
+use crate::database::Storage;
+use crate::models::health::{Health, HealthCheck};
+use crate::services::estate::Estates;
+use crate::services::shutdown::Decryptions;
+
+/// Check whether the server is alive
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Server is running", body = Health),
+    )
+)]
+#[get("/health/live")]
+pub(crate) async fn get_liveness() -> HttpResponse {
+    HttpResponse::Ok().json(Health {
+        status: "ok".to_string(),
+        checks: Vec::new(),
+    })
+}
+
+/// Check whether the server accepts shares
+///
+/// Fails while the database or a layer path is unavailable and during the shutdown.
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Server accepts shares", body = Health),
+        (status = 503, description = "A check failed", body = Health),
+    )
+)]
+#[get("/health/ready")]
+pub(crate) async fn get_readiness(
+    storage: web::Data<dyn Storage>,
+    estates: web::Data<Estates>,
+    decryptions: web::Data<Decryptions>,
+) -> HttpResponse {
+    let mut checks = Vec::new();
+
+    checks.push(match storage.ping().await {
+        Ok(()) => check("database", None),
+        Err(e) => {
+            log::error!("Readiness check of the database failed: {}", e);
+            check(
+                "database",
+                Some("Database doesn't answer queries".to_string()),
+            )
+        }
+    });
+
+    for config in estates.0.iter() {
+        let problem = (!config.layer_path.is_dir()).then(|| {
+            format!(
+                "Layer path of estate {} is not a directory",
+                config.estate.id
+            )
+        });
+        checks.push(check("layer_path", problem));
+    }
+
+    checks.push(check(
+        "shutdown",
+        decryptions.is_draining().then(|| {
+            format!(
+                "Server is shutting down, waiting for {} decryption(s)",
+                decryptions.running()
+            )
+        }),
+    ));
+
+    let ready = checks.iter().all(|check| check.ok);
+    let health = Health {
+        status: if ready { "ok" } else { "unavailable" }.to_string(),
+        checks,
+    };
+    if ready {
+        HttpResponse::Ok().json(health)
+    } else {
+        HttpResponse::ServiceUnavailable().json(health)
+    }
+}
+
+fn check(name: &str, problem: Option<String>) -> HealthCheck {
+    HealthCheck {
+        name: name.to_string(),
+        ok: problem.is_none(),
+        message: problem,
+    }
+}
@@ -1,34 +1,232 @@
-use std::io::Write;
-use std::{fs, path::PathBuf};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::os::unix::fs::DirBuilderExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
-use actix_web::{get, post, web, HttpResponse};
+use actix_web::{get, http::StatusCode, post, web, HttpRequest, HttpResponse};
 use bls12_381_plus::Scalar;
-use sqlx::{Pool, Postgres};
-use vsss_rs::{Feldman, Share};
+use futures_util::{FutureExt, StreamExt};
+use serde_json::json;
+use vsss_rs::Feldman;
 
-use std::process::Command;
+use std::process::Stdio;
 
-use crate::database::shares::{count_shares, insert_share, select_shares};
+use crate::database::Storage;
+use crate::helper::hash::{constant_time_eq, salted_sha256_hex};
+use crate::helper::output::{capture_output_in_background, CapturedOutput, MAX_CAPTURED_OUTPUT};
 use crate::helper::strings::null_terminated_bytes_to_string;
 use crate::helper::vsss::base64_str_to_share;
-use crate::models::layer::{Layer, LayerState};
+use crate::models::event::LayerEvent;
+use crate::models::job::JobState;
+use crate::models::layer::{
+    Layer, LayerCommands, LayerState, LayerStatus, LayerSummary, Placeholders, SecretDelivery,
+    SecretVerifier, ShareAccepted,
+};
+use crate::services::actions::{layer_file, run_builtin_action, secure_delete};
+use crate::services::alert::send_alert;
+use crate::services::audit::{record, ACTOR_SERVER};
+use crate::services::auth::is_admin;
+use crate::services::delivery::deliver_to_beneficiaries;
+use crate::services::error::{ApiError, ErrorCode};
+use crate::services::events::EventBroadcaster;
+use crate::services::guard::ShareGuard;
+use crate::services::sandbox::layer_command;
+use crate::services::secret::SecretHandoff;
+use crate::services::shutdown::Decryptions;
 use crate::Configuration;
 
 async fn decrypt_layer(
-    db_pool: web::Data<Pool<Postgres>>,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
     filepath: &PathBuf,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if decryptions.is_draining() {
+        return Err(format!(
+            "Server is shutting down, decrypt {} again after the restart",
+            filepath.display()
+        )
+        .into());
+    }
+
     // read layer metadata again to make sure it isn't already being decrypted
-    let mut layer = Layer::read_metadata(&filepath)?;
+    let mut layer = Layer::read_metadata(filepath)?;
     if layer.state != LayerState::Idle {
         return Ok(());
     }
 
     // lock layer
     layer.state = LayerState::Decrypting;
-    layer.write_metadata(&filepath)?;
+    layer.write_metadata(filepath)?;
+    broadcaster.publish(LayerEvent::StateChanged {
+        uuid: layer.uuid.clone(),
+        state: layer.state,
+    });
+    record_state_change(storage.get_ref(), &config, &layer).await;
+
+    let job_id = storage
+        .insert_job(&config.estate.id, layer.uuid.clone())
+        .await?;
+    record(
+        storage.get_ref(),
+        &config,
+        "decryption_started",
+        Some(&layer.uuid),
+        ACTOR_SERVER,
+        json!({ "job_id": job_id }),
+    )
+    .await;
+    let _running = Decryptions::track(&decryptions, job_id, &config, filepath);
+
+    let result = run_decryption(
+        storage.get_ref(),
+        &config,
+        &broadcaster,
+        &decryptions,
+        job_id,
+        &layer,
+        filepath,
+    )
+    .await;
+    // the shutdown records the result and unlocks the layer of an interrupted job
+    if decryptions.is_interrupted(job_id) {
+        return Ok(());
+    }
+
+    let (job_state, job_error) = match &result {
+        Ok(()) => (JobState::Succeeded, None),
+        Err(e) => (JobState::Failed, Some(e.to_string())),
+    };
+    if let Err(e) = storage.finish_job(job_id, job_state, job_error).await {
+        log::error!("Failed to store result of job {}: {}", job_id, e);
+    }
+    broadcaster.publish(LayerEvent::JobFinished {
+        uuid: layer.uuid.clone(),
+        job_id,
+        state: job_state,
+    });
+    record(
+        storage.get_ref(),
+        &config,
+        "decryption_finished",
+        Some(&layer.uuid),
+        ACTOR_SERVER,
+        json!({
+            "job_id": job_id,
+            "state": job_state.as_str(),
+            "error": result.as_ref().err().map(|e| e.to_string()),
+        }),
+    )
+    .await;
+    result?;
+
+    if filepath.exists() {
+        layer.state = LayerState::Decrypted;
+        layer.write_metadata(filepath)?;
+        broadcaster.publish(LayerEvent::StateChanged {
+            uuid: layer.uuid.clone(),
+            state: layer.state,
+        });
+        record_state_change(storage.get_ref(), &config, &layer).await;
+    }
+
+    deliver_to_beneficiaries(storage.get_ref(), &config, &broadcaster, &layer, filepath).await;
+
+    unlock_inner_layers(storage, config, broadcaster, decryptions, &layer, filepath).await;
+
+    Ok(())
+}
+
+pub(crate) async fn record_state_change(
+    storage: &dyn Storage,
+    config: &Configuration,
+    layer: &Layer,
+) {
+    record(
+        storage,
+        config,
+        "state_changed",
+        Some(&layer.uuid),
+        ACTOR_SERVER,
+        json!({ "state": layer.state }),
+    )
+    .await;
+}
 
-    let shares = select_shares(&db_pool, layer.uuid.clone()).await?;
+/// Submits shares found in the decrypted output of a layer to the inner layers they belong to.
+async fn unlock_inner_layers(
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
+    layer: &Layer,
+    filepath: &Path,
+) {
+    let working_dir = filepath.parent().unwrap_or(".".as_ref());
+
+    for unlock in layer.unlocks.iter() {
+        // symlinks in the decrypted output must not lead outside of the layer directory
+        let share = match layer_file(working_dir, &unlock.share_file)
+            .and_then(|share_file| fs::read_to_string(share_file).map_err(|e| e.to_string()))
+        {
+            Ok(share) => share.trim_end_matches(&['\r', '\n'][..]).to_string(),
+            Err(e) => {
+                log::error!(
+                    "Failed to read share for inner layer {} from {}: {}",
+                    unlock.layer,
+                    unlock.share_file,
+                    e
+                );
+                continue;
+            }
+        };
+
+        // boxed, as the submitted share may start the decryption of the inner layer
+        let result = submit_share(
+            storage.clone(),
+            config.clone(),
+            broadcaster.clone(),
+            decryptions.clone(),
+            unlock.layer.clone(),
+            share,
+            &format!("layer {}", layer.uuid),
+        )
+        .boxed_local()
+        .await;
+
+        match result {
+            Ok(accepted) => log::info!(
+                "Layer {} provided a share for inner layer {}: {}",
+                layer.uuid,
+                unlock.layer,
+                accepted.message
+            ),
+            Err(e) => log::error!(
+                "Layer {} failed to provide a share for inner layer {}: {}",
+                layer.uuid,
+                unlock.layer,
+                e
+            ),
+        }
+    }
+}
+
+async fn run_decryption(
+    storage: &dyn Storage,
+    config: &Configuration,
+    broadcaster: &EventBroadcaster,
+    decryptions: &Decryptions,
+    job_id: i32,
+    layer: &Layer,
+    filepath: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let shares = storage
+        .select_shares(&config.estate.id, layer.uuid.clone())
+        .await?;
 
     let threshold = layer.vsss.as_ref().map(|v| v.threshold).unwrap_or(1);
 
@@ -56,153 +254,769 @@ async fn decrypt_layer(
         shares.first().unwrap().to_string() // asserted: shares.len() > 1
     };
 
-    let working_dir = filepath.parent().unwrap_or(".".as_ref());
+    let working_dir = filepath.parent().unwrap_or(".".as_ref()).canonicalize()?;
+
+    if let Some(output_dir) = layer.output_dir.as_ref() {
+        fs::create_dir_all(layer_file(&working_dir, output_dir)?)?;
+    }
+    // intermediate files of the commands, overwritten and removed afterwards
+    let scratch_dir = scratch_dir(&working_dir, job_id);
+    fs::DirBuilder::new().mode(0o700).create(&scratch_dir)?;
+    let placeholders = layer.placeholders(&working_dir, &scratch_dir);
+
+    let result = run_commands(
+        storage,
+        config,
+        broadcaster,
+        decryptions,
+        job_id,
+        layer,
+        &placeholders,
+        &secret,
+    )
+    .await;
+
+    if let Err(e) = secure_delete(&scratch_dir) {
+        log::error!(
+            "Failed to remove scratch directory {}: {}",
+            scratch_dir.display(),
+            e
+        );
+    }
+    result
+}
+
+// never listed, downloaded or delivered
+pub(crate) const SCRATCH_PREFIX: &str = ".scratch-";
+
+/// Directory of the intermediate files of a decryption job
+pub(crate) fn scratch_dir(layer_dir: &Path, job_id: i32) -> PathBuf {
+    layer_dir.join(format!("{}{}", SCRATCH_PREFIX, job_id))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_commands(
+    storage: &dyn Storage,
+    config: &Configuration,
+    broadcaster: &EventBroadcaster,
+    decryptions: &Decryptions,
+    job_id: i32,
+    layer: &Layer,
+    placeholders: &Placeholders,
+    secret: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let working_dir = Path::new(&placeholders.layer_dir);
 
     // call commands for decryption process
-    for command in layer.commands.iter() {
-        let action_working_dir = if command.working_dir.is_empty() {
-            working_dir.to_path_buf()
-        } else {
-            if command.working_dir.starts_with("/") {
-                PathBuf::from(&command.working_dir)
-            } else {
-                working_dir.join(&command.working_dir)
-            }
+    for (step, command) in layer.commands.iter().enumerate() {
+        if decryptions.is_interrupted(job_id) {
+            return Err("Interrupted by the shutdown of the server".into());
+        }
+        broadcaster.publish(LayerEvent::DecryptionProgress {
+            uuid: layer.uuid.clone(),
+            job_id,
+            step: step + 1,
+            steps: layer.commands.len(),
+        });
+
+        let handoff = match command {
+            LayerCommands::Exec {
+                secret: delivery, ..
+            } => Some(
+                SecretHandoff::prepare(delivery, secret, &config.secret_dir)
+                    .map_err(|e| format!("Failed to hand over the secret: {}", e))?,
+            ),
+            _ => None,
         };
+        let secret_file = handoff.as_ref().and_then(|handoff| handoff.file());
+        let command = command.expand(&Placeholders {
+            secret_file: secret_file.map(|file| file.display().to_string()),
+            ..placeholders.clone()
+        })?;
 
-        let mut process = Command::new(command.program.clone())
-            .args(command.args.clone())
-            .current_dir(action_working_dir)
-            .stdin(std::process::Stdio::piped())
-            .spawn()?;
-
-        if command.secret_stdin {
-            let maybe_stdin = process.stdin.take();
-            if maybe_stdin.is_none() {
-                let _ = process.kill();
-                return Err("Unable to write stdin of process".into());
-            }
-            let mut stdin = maybe_stdin.unwrap();
-            stdin.write_all(secret.as_bytes())?;
-        }
+        let (exit_status, stdout, stderr, result) = match &command {
+            LayerCommands::Exec {
+                program,
+                args,
+                working_dir: command_working_dir,
+                secret: delivery,
+            } => {
+                let action_working_dir = if command_working_dir.is_empty() {
+                    working_dir.to_path_buf()
+                } else {
+                    if command_working_dir.starts_with("/") {
+                        PathBuf::from(command_working_dir)
+                    } else {
+                        working_dir.join(command_working_dir)
+                    }
+                };
 
-        let status = process.wait()?;
+                let mut process = layer_command(
+                    &config.sandbox,
+                    layer,
+                    working_dir,
+                    &action_working_dir,
+                    program,
+                    args,
+                    secret_file,
+                )?;
+                if let Some(handoff) = handoff.as_ref() {
+                    handoff.apply(&mut process);
+                }
+                let mut process = process
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
 
-        if !status.success() {
-            return Err(format!(
-                "Action \"{}\" returned exit status {}",
-                command.program, status
+                let stdout_capture = capture_output_in_background(
+                    process.stdout.take(),
+                    MAX_CAPTURED_OUTPUT,
+                    secret.to_string(),
+                );
+                let stderr_capture = capture_output_in_background(
+                    process.stderr.take(),
+                    MAX_CAPTURED_OUTPUT,
+                    secret.to_string(),
+                );
+
+                let stdin = process.stdin.take();
+                // killed by the shutdown if the job is interrupted
+                decryptions.register_process(job_id, process)?;
+
+                // a process exiting without reading stdin still has to be reaped and recorded
+                let stdin_result = match (delivery, stdin) {
+                    (SecretDelivery::Stdin, Some(mut stdin)) => stdin.write_all(secret.as_bytes()),
+                    (SecretDelivery::Stdin, None) => Err(io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "stdin of process isn't piped",
+                    )),
+                    _ => Ok(()),
+                };
+
+                let status = decryptions.wait_process(job_id).await?;
+
+                let stdout = stdout_capture
+                    .join()
+                    .map_err(|_| "Failed to capture stdout of process")??;
+                let stderr = stderr_capture
+                    .join()
+                    .map_err(|_| "Failed to capture stderr of process")??;
+
+                let result = match stdin_result {
+                    _ if !status.success() => Err(format!(
+                        "Action \"{}\" returned exit status {}",
+                        program, status
+                    )),
+                    Err(e) => Err(format!(
+                        "Failed to write the secret to stdin of action \"{}\": {}",
+                        program, e
+                    )),
+                    Ok(()) => Ok(()),
+                };
+                (status.code(), stdout, stderr, result)
+            }
+            // built-in actions aren't processes, they have no exit status
+            _ => match run_blocking_action(&command, working_dir, secret).await {
+                Ok(summary) => (
+                    None,
+                    CapturedOutput {
+                        text: summary,
+                        truncated: false,
+                    },
+                    CapturedOutput::default(),
+                    Ok(()),
+                ),
+                Err(e) => (
+                    None,
+                    CapturedOutput::default(),
+                    CapturedOutput {
+                        text: e.clone(),
+                        truncated: false,
+                    },
+                    Err(format!("Action \"{}\" failed: {}", command.name(), e)),
+                ),
+            },
+        };
+
+        storage
+            .insert_job_command(
+                job_id,
+                command.name().to_string(),
+                exit_status,
+                stdout,
+                stderr,
             )
-            .into());
-        }
-    }
+            .await?;
 
-    if filepath.exists() {
-        layer.state = LayerState::Decrypted;
-        layer.write_metadata(&filepath)?;
+        result?;
     }
 
     Ok(())
 }
 
+// built-in actions decrypt, extract and overwrite files, which blocks
+async fn run_blocking_action(
+    command: &LayerCommands,
+    working_dir: &Path,
+    secret: &str,
+) -> Result<String, String> {
+    let command = command.clone();
+    let working_dir = working_dir.to_path_buf();
+    let secret = secret.to_string();
+    web::block(move || {
+        run_builtin_action(&command, &working_dir, &secret).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|result| result)
+}
+
+/// Submit a VSSS share for a layer
+///
+/// Decryption starts in the background as soon as the threshold is reached.
+#[utoipa::path(
+    request_body(
+        content = String,
+        content_type = "text/plain",
+        description = "Base64 encoded VSSS share, or the secret itself for layers with a threshold of 1"
+    ),
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Share accepted, threshold hasn't been reached yet", body = ShareAccepted),
+        (status = 202, description = "Share accepted, threshold reached and decryption started", body = ShareAccepted),
+        (status = 400, description = "Malformed or invalid share", body = ApiError),
+        (status = 404, description = "Layer not found", body = ApiError),
+        (status = 409, description = "Duplicate share or layer is being decrypted", body = ApiError),
+        (status = 410, description = "Layer has already been decrypted", body = ApiError),
+        (status = 413, description = "Share exceeds the size limit", body = ApiError),
+        (status = 429, description = "Rate limited or locked out after invalid shares, see Retry-After", body = ApiError),
+        (status = 503, description = "Server is shutting down, see Retry-After", body = ApiError),
+    )
+)]
 #[post("/layer/{uuid}/share")]
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn provide_share_for_layer(
-    db_pool: web::Data<Pool<Postgres>>,
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
     config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    guard: web::Data<ShareGuard>,
+    decryptions: web::Data<Decryptions>,
     layer_uuid: web::Path<String>,
-    share_str: String,
-) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if let Some((filepath, layer)) =
-        find_layer_file(config.layer_path.clone(), layer_uuid.to_string())?
-    {
-        let mut threshold = 1;
-        if let Some(vsss) = layer.vsss.as_ref() {
-            let share = base64::decode(share_str.clone())?;
-            let share = Share::try_from(&share[..])?;
-            if !vsss.feldman_verifier.verify(&share) {
-                return Ok(HttpResponse::BadRequest().body("Invalid share"));
-            }
-            threshold = vsss.threshold;
+    payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    if decryptions.is_draining() {
+        return Err(ApiError::shutting_down());
+    }
+    let layer_uuid = layer_uuid.into_inner();
+    let ip = guard.client_ip(&req);
+
+    guard.check(&config.estate.id, ip, &layer_uuid)?;
+
+    let peer = ip
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let result = match read_share(payload, guard.max_share_size()).await {
+        Ok(share_str) => {
+            submit_share(
+                storage.clone(),
+                config.clone(),
+                broadcaster.clone(),
+                decryptions.clone(),
+                layer_uuid.clone(),
+                share_str,
+                &peer,
+            )
+            .await
         }
+        Err(e) => {
+            record(
+                storage.get_ref(),
+                &config,
+                "share_rejected",
+                Some(&layer_uuid),
+                &peer,
+                json!({ "reason": e.message }),
+            )
+            .await;
+            Err(e)
+        }
+    };
 
-        if insert_share(&db_pool, layer_uuid.to_string(), share_str).await? {
-            let res = count_shares(&db_pool, layer_uuid.to_string()).await?;
-
-            if let Some(count) = res {
-                if count >= threshold as i64 {
-                    actix_rt::spawn(async move {
-                        if let Err(e) = decrypt_layer(db_pool, &filepath).await {
-                            log::error!("Decryption failed: {}", e);
-                            let mut layer = layer;
-                            layer.state = LayerState::Idle;
-                            let _ = layer.write_metadata(&filepath);
-                        }
-                    });
-
-                    return Ok(
-                        HttpResponse::Ok().body("Share accepted, threshold reached. Decrypting.")
-                    );
-                }
+    let accepted = match result {
+        Ok(accepted) => accepted,
+        Err(e) => {
+            if matches!(
+                e.code,
+                ErrorCode::LayerNotFound
+                    | ErrorCode::MalformedShare
+                    | ErrorCode::InvalidShare
+                    | ErrorCode::PayloadTooLarge
+            ) {
+                reject_share(
+                    storage.get_ref(),
+                    &config,
+                    &broadcaster,
+                    &guard,
+                    ip,
+                    &layer_uuid,
+                    &e,
+                )
+                .await;
             }
+            return Err(e);
+        }
+    };
+
+    if accepted.layer.state == LayerState::Decrypting {
+        Ok(HttpResponse::Accepted().json(accepted))
+    } else {
+        Ok(HttpResponse::Ok().json(accepted))
+    }
+}
+
+/// Reads the submitted share without buffering more than the allowed size.
+async fn read_share(mut payload: web::Payload, limit: usize) -> Result<String, ApiError> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|_| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::MalformedShare,
+                "Failed to read the share",
+            )
+        })?;
+        if body.len() + chunk.len() > limit {
+            return Err(ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                ErrorCode::PayloadTooLarge,
+                format!("Share exceeds {} bytes", limit),
+            ));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(body.to_vec()).map_err(|_| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::MalformedShare,
+            "Share is not valid UTF-8",
+        )
+    })
+}
+
+/// Logs, publishes and counts a rejected share, and alerts if the address got locked out.
+async fn reject_share(
+    storage: &dyn Storage,
+    config: &Configuration,
+    broadcaster: &EventBroadcaster,
+    guard: &ShareGuard,
+    ip: Option<IpAddr>,
+    layer_uuid: &str,
+    error: &ApiError,
+) {
+    let peer = ip
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    log::warn!(
+        "Rejected share for layer {} from {}: {}",
+        layer_uuid,
+        peer,
+        error.message
+    );
+
+    let locked_out = guard.record_invalid(&config.estate.id, ip);
+
+    // unknown layers are not published, the UUID is chosen by the client
+    if error.code != ErrorCode::LayerNotFound {
+        broadcaster.publish(LayerEvent::ShareRejected {
+            uuid: layer_uuid.to_string(),
+            reason: error.message.clone(),
+            locked_out,
+        });
+    }
+
+    if locked_out {
+        record(
+            storage,
+            config,
+            "address_locked_out",
+            Some(layer_uuid),
+            &peer,
+            json!({ "reason": error.message }),
+        )
+        .await;
+        send_alert(
+            config,
+            format!("{} locked out after repeated invalid shares", peer),
+            format!(
+                "The address {} submitted too many malformed or invalid shares and has been locked out. The last attempt was for layer {}: {}\n",
+                peer, layer_uuid, error.message
+            ),
+        );
+    }
+}
+
+/// Verifies and stores a share, and starts the decryption of the layer once the threshold is reached.
+/// The outcome is recorded in the audit log with the submitting actor.
+pub(crate) async fn submit_share(
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
+    layer_uuid: String,
+    share_str: String,
+    actor: &str,
+) -> Result<ShareAccepted, ApiError> {
+    let result = accept_share(
+        storage.clone(),
+        config.clone(),
+        broadcaster,
+        decryptions,
+        layer_uuid.clone(),
+        share_str,
+    )
+    .await;
+
+    let (event, details) = match &result {
+        Ok(accepted) => (
+            "share_accepted",
+            json!({
+                "shares_received": accepted.layer.shares_received,
+                "shares_required": accepted.layer.shares_required,
+            }),
+        ),
+        Err(e) if e.code == ErrorCode::InternalError => return result,
+        Err(e) if e.code == ErrorCode::DuplicateShare => ("share_duplicate", json!({})),
+        Err(e) => ("share_rejected", json!({ "reason": e.message })),
+    };
+    record(
+        storage.get_ref(),
+        &config,
+        event,
+        Some(&layer_uuid),
+        actor,
+        details,
+    )
+    .await;
+
+    result
+}
+
+async fn accept_share(
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
+    layer_uuid: String,
+    share_str: String,
+) -> Result<ShareAccepted, ApiError> {
+    let (filepath, layer) = find_layer_file(
+        config.layer_path.clone(),
+        config.layer_suffix.clone(),
+        layer_uuid.clone(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(&layer_uuid))?;
 
-            return Ok(
-                HttpResponse::Ok().body("Share accepted, threshold hasn't been reached yet.")
-            );
+    ensure_layer_is_idle(&layer)?;
+
+    // the stored share is the secret of layers without VSSS, it has to be what was verified
+    let share_str = share_str.trim().to_string();
+    if let Some(vsss) = layer.vsss.as_ref() {
+        let share = base64_str_to_share(&share_str).map_err(|_| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::MalformedShare,
+                "Share is not a base64 encoded VSSS share",
+            )
+        })?;
+        if !vsss.feldman_verifier.verify(&share) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidShare,
+                "Share failed Feldman verification",
+            ));
+        }
+    } else if let Some(secret_verifier) = layer.secret_verifier.as_ref() {
+        if !secret_matches(secret_verifier, &share_str) {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidShare,
+                "Secret doesn't match the layer",
+            ));
         }
-        return Ok(HttpResponse::InternalServerError().body("Failed to store share"));
     } else {
-        return Ok(HttpResponse::NotFound().finish());
+        log::warn!(
+            "Layer {} has neither VSSS metadata nor a secret verifier, accepting the secret unverified",
+            layer.uuid
+        );
     }
+
+    if !storage
+        .insert_share(&config.estate.id, layer_uuid, share_str)
+        .await?
+    {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::DuplicateShare,
+            "Share has already been provided for this layer",
+        ));
+    }
+
+    let mut status = read_layer_status(storage.get_ref(), &config.estate.id, &layer).await?;
+    broadcaster.publish(LayerEvent::ShareReceived {
+        uuid: status.uuid.clone(),
+        shares_received: status.shares_received,
+        shares_required: status.shares_required,
+    });
+
+    if status.shares_received >= status.shares_required as i64 {
+        start_decryption(storage, config, broadcaster, decryptions, filepath, layer);
+
+        status.state = LayerState::Decrypting;
+        return Ok(ShareAccepted {
+            message: "Share accepted, threshold reached. Decrypting.".to_string(),
+            layer: status,
+        });
+    }
+
+    Ok(ShareAccepted {
+        message: "Share accepted, threshold hasn't been reached yet.".to_string(),
+        layer: status,
+    })
 }
 
+/// Decrypts a layer in the background and unlocks it again if the decryption fails.
+pub(crate) fn start_decryption(
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    broadcaster: web::Data<EventBroadcaster>,
+    decryptions: web::Data<Decryptions>,
+    filepath: PathBuf,
+    layer: Layer,
+) {
+    actix_rt::spawn(async move {
+        if let Err(e) = decrypt_layer(
+            storage.clone(),
+            config.clone(),
+            broadcaster.clone(),
+            decryptions,
+            &filepath,
+        )
+        .await
+        {
+            log::error!("Decryption failed: {}", e);
+            let mut layer = layer;
+            layer.state = LayerState::Idle;
+            let _ = layer.write_metadata(&filepath);
+            broadcaster.publish(LayerEvent::StateChanged {
+                uuid: layer.uuid.clone(),
+                state: layer.state,
+            });
+            record_state_change(storage.get_ref(), &config, &layer).await;
+        }
+    });
+}
+
+fn secret_matches(secret_verifier: &SecretVerifier, secret: &str) -> bool {
+    match base64::decode(&secret_verifier.salt) {
+        Ok(salt) => constant_time_eq(
+            salted_sha256_hex(&salt, secret.as_bytes()).as_bytes(),
+            secret_verifier.sha256.as_bytes(),
+        ),
+        Err(_) => false,
+    }
+}
+
+/// List all layers with their public metadata
+#[utoipa::path(
+    responses(
+        (status = 200, description = "UUID, state and threshold of all layers", body = [LayerSummary]),
+    )
+)]
 #[get("/layers")]
 pub(crate) async fn get_available_layers(
     config: web::Data<Configuration>,
-) -> Result<HttpResponse, actix_web::Error> {
-    Ok(HttpResponse::Ok().json(rec_read_layer_files(
+) -> Result<HttpResponse, ApiError> {
+    let layers = rec_read_layer_files(config.layer_path.clone(), config.layer_suffix.clone())?;
+    let layers: Vec<LayerSummary> = layers.iter().map(LayerSummary::from).collect();
+
+    Ok(HttpResponse::Ok().json(layers))
+}
+
+/// Get the public metadata of a layer
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "UUID, state and threshold of the layer", body = LayerSummary),
+        (status = 404, description = "Layer not found", body = ApiError),
+    )
+)]
+#[get("/layer/{uuid}")]
+pub(crate) async fn get_layer(
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, layer) = find_layer_file(
         config.layer_path.clone(),
         config.layer_suffix.clone(),
-    )?))
+        layer_uuid.to_string(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(&layer_uuid))?;
+
+    Ok(HttpResponse::Ok().json(LayerSummary::from(&layer)))
 }
 
-fn rec_read_layer_files(path: PathBuf, suffix: String) -> Result<Vec<Layer>, actix_web::Error> {
-    let mut files: Vec<Layer> = Vec::new();
+/// Get received and required share counts and the latest decryption job of a layer
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Layer status", body = LayerStatus),
+        (status = 404, description = "Layer not found", body = ApiError),
+    )
+)]
+#[get("/layer/{uuid}/status")]
+pub(crate) async fn get_layer_status(
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let (_, layer) = find_layer_file(
+        config.layer_path.clone(),
+        config.layer_suffix.clone(),
+        layer_uuid.to_string(),
+    )?
+    .ok_or_else(|| ApiError::layer_not_found(&layer_uuid))?;
+
+    Ok(HttpResponse::Ok()
+        .json(read_layer_status(storage.get_ref(), &config.estate.id, &layer).await?))
+}
+
+/// List decryption jobs of a layer including the captured command output
+#[utoipa::path(
+    params(("uuid", description = "Layer UUID")),
+    responses(
+        (status = 200, description = "Decryption jobs, newest first", body = [Job]),
+        (status = 401, description = "Missing or invalid admin token", body = ApiError),
+    ),
+    security(("admin_token" = []))
+)]
+#[get("/layer/{uuid}/jobs")]
+pub(crate) async fn get_layer_jobs(
+    req: HttpRequest,
+    storage: web::Data<dyn Storage>,
+    config: web::Data<Configuration>,
+    layer_uuid: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    if !is_admin(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+
+    let jobs = storage
+        .select_jobs(&config.estate.id, layer_uuid.to_string())
+        .await?;
 
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+pub(crate) fn ensure_layer_is_idle(layer: &Layer) -> Result<(), ApiError> {
+    match layer.state {
+        LayerState::Idle => Ok(()),
+        LayerState::Decrypting => Err(ApiError::new(
+            StatusCode::CONFLICT,
+            ErrorCode::LayerDecrypting,
+            "Layer is currently being decrypted",
+        )),
+        LayerState::Decrypted => Err(ApiError::new(
+            StatusCode::GONE,
+            ErrorCode::LayerDecrypted,
+            "Layer has already been decrypted",
+        )),
+        LayerState::Retired => Err(ApiError::new(
+            StatusCode::GONE,
+            ErrorCode::LayerRetired,
+            "Layer has been retired",
+        )),
+    }
+}
+
+pub(crate) async fn read_layer_status(
+    storage: &dyn Storage,
+    estate: &str,
+    layer: &Layer,
+) -> Result<LayerStatus, sqlx::Error> {
+    let shares_received = storage
+        .count_shares(estate, layer.uuid.clone())
+        .await?
+        .unwrap_or(0);
+    let job = storage
+        .select_latest_job(estate, layer.uuid.clone())
+        .await?;
+
+    Ok(LayerStatus {
+        uuid: layer.uuid.clone(),
+        state: layer.state,
+        shares_received,
+        shares_required: layer.vsss.as_ref().map(|v| v.threshold).unwrap_or(1),
+        job,
+    })
+}
+
+pub(crate) fn rec_read_layer_files(
+    path: PathBuf,
+    suffix: String,
+) -> Result<Vec<Layer>, std::io::Error> {
+    let mut layers = Vec::new();
+    let mut errors = Vec::new();
+    rec_scan_layer_files(&path, &suffix, &mut layers, &mut errors)?;
+
+    for (file, e) in errors {
+        log::warn!("Failed to read YAML file {}: {}", file.display(), e);
+    }
+
+    Ok(layers.into_iter().map(|(_, layer)| layer).collect())
+}
+
+/// Collects layers with their files, and the files that couldn't be read.
+pub(crate) fn rec_scan_layer_files(
+    path: &PathBuf,
+    suffix: &str,
+    layers: &mut Vec<(PathBuf, Layer)>,
+    errors: &mut Vec<(PathBuf, String)>,
+) -> Result<(), std::io::Error> {
     let dir_entries = fs::read_dir(path)?;
 
     for dir_entry in dir_entries {
         let dir_entry = dir_entry?;
         if dir_entry.metadata()?.is_dir() {
-            files.append(&mut rec_read_layer_files(dir_entry.path(), suffix.clone())?);
-        } else if dir_entry.file_name().to_string_lossy().ends_with(&suffix) {
-            if let Ok(layer) = Layer::read_metadata(&dir_entry.path()) {
-                files.push(layer);
-            } else {
-                log::warn!("Failed to read YAML file: {}", dir_entry.path().display());
+            rec_scan_layer_files(&dir_entry.path(), suffix, layers, errors)?;
+        } else if dir_entry.file_name().to_string_lossy().ends_with(suffix) {
+            match Layer::read_metadata(&dir_entry.path()) {
+                Ok(layer) => layers.push((dir_entry.path(), layer)),
+                Err(e) => errors.push((dir_entry.path(), e.to_string())),
             }
         }
     }
 
-    Ok(files)
+    Ok(())
 }
 
-fn find_layer_file(
+pub(crate) fn find_layer_file(
     path: PathBuf,
+    suffix: String,
     uuid: String,
-) -> Result<Option<(PathBuf, Layer)>, actix_web::Error> {
+) -> Result<Option<(PathBuf, Layer)>, std::io::Error> {
     let dir_entries = fs::read_dir(path)?;
 
     for dir_entry in dir_entries {
         let dir_entry = dir_entry?;
         if dir_entry.metadata()?.is_dir() {
-            if let Some(result) = find_layer_file(dir_entry.path(), uuid.clone())? {
+            if let Some(result) = find_layer_file(dir_entry.path(), suffix.clone(), uuid.clone())? {
                 return Ok(Some(result));
             }
-        } else if dir_entry
-            .file_name()
-            .to_string_lossy()
-            .ends_with(".layer.yml")
-        {
+        } else if dir_entry.file_name().to_string_lossy().ends_with(&suffix) {
             if let Ok(layer) = Layer::read_metadata(&dir_entry.path()) {
                 if layer.uuid == uuid {
                     return Ok(Some((dir_entry.path(), layer)));
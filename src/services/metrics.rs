@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use actix_web::{get, http::header::ContentType, web, HttpResponse};
+
+use crate::database::Storage;
+use crate::models::job::JobState;
+use crate::models::layer::{Layer, LayerState};
+use crate::prometheus::MetricsWriter;
+use crate::services::error::ApiError;
+use crate::services::estate::Estates;
+use crate::services::guard::ShareGuard;
+use crate::services::layer::rec_read_layer_files;
+
+// audit log events counted as share submissions, by result
+const SHARE_SUBMISSION_EVENTS: [(&str, &str); 3] = [
+    ("share_accepted", "accepted"),
+    ("share_rejected", "rejected"),
+    ("share_duplicate", "duplicate"),
+];
+
+/// Metrics in the Prometheus text format
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text exposition format", body = String, content_type = "text/plain"),
+    )
+)]
+#[get("/metrics")]
+pub(crate) async fn get_metrics(
+    storage: web::Data<dyn Storage>,
+    estates: web::Data<Estates>,
+    guard: web::Data<ShareGuard>,
+) -> Result<HttpResponse, ApiError> {
+    let mut metrics = MetricsWriter::new();
+
+    let mut layers = Vec::new();
+    for config in estates.0.iter() {
+        for layer in rec_read_layer_files(config.layer_path.clone(), config.layer_suffix.clone())? {
+            layers.push((config.estate.id.clone(), layer));
+        }
+    }
+
+    metrics.family("perimetr_layers", "gauge", "Number of layers by state");
+    for config in estates.0.iter() {
+        for state in [
+            LayerState::Idle,
+            LayerState::Decrypting,
+            LayerState::Decrypted,
+            LayerState::Retired,
+        ] {
+            let count = layers
+                .iter()
+                .filter(|(estate, layer)| *estate == config.estate.id && layer.state == state)
+                .count();
+            metrics.sample(
+                "perimetr_layers",
+                &[
+                    ("estate", &config.estate.id),
+                    ("state", layer_state_label(state)),
+                ],
+                count as f64,
+            );
+        }
+    }
+
+    metrics.family(
+        "perimetr_layer_shares_required",
+        "gauge",
+        "Shares required to decrypt a layer",
+    );
+    for (estate, layer) in layers.iter() {
+        let threshold = layer.vsss.as_ref().map(|v| v.threshold).unwrap_or(1);
+        metrics.sample(
+            "perimetr_layer_shares_required",
+            &[("estate", estate), ("layer", &layer.uuid)],
+            threshold as f64,
+        );
+    }
+
+    metrics.family(
+        "perimetr_share_submissions_throttled_total",
+        "counter",
+        "Share submissions refused without verification since the start of the server",
+    );
+    for config in estates.0.iter() {
+        let throttled = guard.rejected_submissions(&config.estate.id);
+        metrics.sample(
+            "perimetr_share_submissions_throttled_total",
+            &[("estate", &config.estate.id), ("reason", "rate_limited")],
+            throttled.rate_limited as f64,
+        );
+        metrics.sample(
+            "perimetr_share_submissions_throttled_total",
+            &[("estate", &config.estate.id), ("reason", "locked_out")],
+            throttled.locked_out as f64,
+        );
+    }
+
+    // the database is reported as down instead of failing the whole scrape
+    let database_up = storage.ping().await.is_ok();
+    metrics.family(
+        "perimetr_database_up",
+        "gauge",
+        "Whether the database answers queries",
+    );
+    metrics.sample("perimetr_database_up", &[], database_up as u8 as f64);
+
+    metrics.family(
+        "perimetr_database_pool_connections",
+        "gauge",
+        "Open connections of the database pool by state",
+    );
+    let pool_stats = storage.pool_stats();
+    metrics.sample(
+        "perimetr_database_pool_connections",
+        &[("state", "idle")],
+        pool_stats.idle as f64,
+    );
+    metrics.sample(
+        "perimetr_database_pool_connections",
+        &[("state", "active")],
+        pool_stats.size.saturating_sub(pool_stats.idle) as f64,
+    );
+    metrics.family(
+        "perimetr_database_pool_max_connections",
+        "gauge",
+        "Maximum connections of the database pool",
+    );
+    metrics.sample(
+        "perimetr_database_pool_max_connections",
+        &[],
+        pool_stats.max_connections as f64,
+    );
+
+    if database_up {
+        write_database_metrics(&mut metrics, storage.get_ref(), &estates, &layers).await?;
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType(
+            "text/plain; version=0.0.4; charset=utf-8".parse().unwrap(),
+        ))
+        .body(metrics.finish()))
+}
+
+async fn write_database_metrics(
+    metrics: &mut MetricsWriter,
+    storage: &dyn Storage,
+    estates: &Estates,
+    layers: &[(String, Layer)],
+) -> Result<(), sqlx::Error> {
+    let shares: HashMap<(String, String), i64> = storage
+        .count_shares_by_layer()
+        .await?
+        .into_iter()
+        .map(|(estate, layer_uuid, count)| ((estate, layer_uuid), count))
+        .collect();
+    metrics.family(
+        "perimetr_layer_shares_received",
+        "gauge",
+        "Shares received for a layer",
+    );
+    for (estate, layer) in layers.iter() {
+        let count = shares.get(&(estate.clone(), layer.uuid.clone()));
+        metrics.sample(
+            "perimetr_layer_shares_received",
+            &[("estate", estate), ("layer", &layer.uuid)],
+            *count.unwrap_or(&0) as f64,
+        );
+    }
+
+    let mut events = vec!["address_locked_out"];
+    events.extend(SHARE_SUBMISSION_EVENTS.iter().map(|(event, _)| *event));
+    let event_counts: HashMap<(String, String), i64> = storage
+        .count_audit_events(&events)
+        .await?
+        .into_iter()
+        .map(|(estate, event, count)| ((estate, event), count))
+        .collect();
+    let event_count = |estate: &str, event: &str| {
+        *event_counts
+            .get(&(estate.to_string(), event.to_string()))
+            .unwrap_or(&0) as f64
+    };
+    metrics.family(
+        "perimetr_share_submissions_total",
+        "counter",
+        "Verified share submissions by result, from the audit log",
+    );
+    for config in estates.0.iter() {
+        for (event, result) in SHARE_SUBMISSION_EVENTS.iter() {
+            metrics.sample(
+                "perimetr_share_submissions_total",
+                &[("estate", &config.estate.id), ("result", result)],
+                event_count(&config.estate.id, event),
+            );
+        }
+    }
+    metrics.family(
+        "perimetr_address_lockouts_total",
+        "counter",
+        "Addresses locked out after repeated invalid shares, from the audit log",
+    );
+    for config in estates.0.iter() {
+        metrics.sample(
+            "perimetr_address_lockouts_total",
+            &[("estate", &config.estate.id)],
+            event_count(&config.estate.id, "address_locked_out"),
+        );
+    }
+
+    let job_stats = storage.select_job_stats().await?;
+    metrics.family(
+        "perimetr_decryption_job_duration_seconds",
+        "summary",
+        "Duration of decryption jobs by outcome",
+    );
+    for config in estates.0.iter() {
+        for state in [JobState::Running, JobState::Succeeded, JobState::Failed] {
+            let stats = job_stats
+                .iter()
+                .find(|stats| stats.estate == config.estate.id && stats.state == state.as_str());
+            let labels = [
+                ("estate", config.estate.id.as_str()),
+                ("state", state.as_str()),
+            ];
+            metrics.sample(
+                "perimetr_decryption_job_duration_seconds_sum",
+                &labels,
+                stats.map(|stats| stats.duration_sum).unwrap_or(0.0),
+            );
+            metrics.sample(
+                "perimetr_decryption_job_duration_seconds_count",
+                &labels,
+                stats.map(|stats| stats.count).unwrap_or(0) as f64,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn layer_state_label(state: LayerState) -> &'static str {
+    match state {
+        LayerState::Idle => "idle",
+        LayerState::Decrypting => "decrypting",
+        LayerState::Decrypted => "decrypted",
+        LayerState::Retired => "retired",
+    }
+}
@@ -1 +1,18 @@
+pub(crate) mod actions;
+pub(crate) mod admin;
+pub(crate) mod alert;
+pub(crate) mod audit;
+pub(crate) mod auth;
+pub(crate) mod data;
+pub(crate) mod delivery;
+pub(crate) mod error;
+pub(crate) mod estate;
+pub(crate) mod events;
+pub(crate) mod guard;
+pub(crate) mod health;
 pub(crate) mod layer;
+pub(crate) mod metrics;
+pub(crate) mod openapi;
+pub(crate) mod sandbox;
+pub(crate) mod secret;
+pub(crate) mod shutdown;
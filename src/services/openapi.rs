@@ -0,0 +1,135 @@
+use actix_web::{get, HttpResponse};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::models::admin::{AdminResult, RescanError, RescanReport, ShareRecord};
+use crate::models::audit::{AuditEntry, AuditExport};
+use crate::models::delivery::{Delivery, DeliveryState};
+use crate::models::estate::{Branding, EstateInfo};
+use crate::models::event::LayerEvent;
+use crate::models::health::{Health, HealthCheck};
+use crate::models::job::{Job, JobCommand, JobState, JobSummary};
+use crate::models::layer::{
+    Beneficiary, DeliveryMethod, DownloadToken, Layer, LayerAccess, LayerCommands, LayerSandbox,
+    LayerState, LayerStatus, LayerSummary, LayerUnlock, LayerVisibility, ShareAccepted,
+    VSSSMetadata,
+};
+use crate::services::data::{DownloadLink, DownloadLinkRequest};
+use crate::services::error::{ApiError, ErrorCode};
+use crate::services::{admin, audit, data, delivery, estate, events, health, layer, metrics};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "perimetr",
+        description = "Webservice that accepts VSSS shares for perimetr layers and decrypts them when enough shares are received. A server hosting several estates serves every path except /estates, /health, /metrics and /openapi.json below /estates/{id}."
+    ),
+    paths(
+        estate::get_estate,
+        estate::list_estates,
+        layer::get_available_layers,
+        layer::get_layer,
+        layer::get_layer_status,
+        layer::provide_share_for_layer,
+        layer::get_layer_jobs,
+        events::get_events,
+        data::download_file,
+        data::list_files,
+        data::create_download_link,
+        delivery::get_layer_deliveries,
+        audit::get_audit_log,
+        metrics::get_metrics,
+        health::get_liveness,
+        health::get_readiness,
+    ),
+    components(schemas(
+        ApiError,
+        AuditEntry,
+        AuditExport,
+        Beneficiary,
+        Branding,
+        Delivery,
+        DeliveryMethod,
+        DeliveryState,
+        DownloadLink,
+        DownloadLinkRequest,
+        DownloadToken,
+        ErrorCode,
+        EstateInfo,
+        Health,
+        HealthCheck,
+        Job,
+        JobCommand,
+        JobState,
+        JobSummary,
+        Layer,
+        LayerAccess,
+        LayerCommands,
+        LayerEvent,
+        LayerSandbox,
+        LayerState,
+        LayerStatus,
+        LayerSummary,
+        LayerUnlock,
+        LayerVisibility,
+        ShareAccepted,
+        VSSSMetadata,
+    )),
+    modifiers(&AdminTokenAddon),
+)]
+pub(crate) struct ApiDoc;
+
+/// Administrative API, served on the admin socket only
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "perimetr admin",
+        description = "Administrative operations on perimetr layers. A server hosting several estates serves every path except /openapi.json below /estates/{id}."
+    ),
+    paths(
+        admin::list_layers,
+        admin::reset_layer,
+        admin::retire_layer,
+        admin::decrypt_layer_again,
+        admin::list_shares,
+        admin::purge_shares,
+        admin::rescan_layers,
+    ),
+    components(schemas(
+        AdminResult,
+        ApiError,
+        ErrorCode,
+        JobState,
+        JobSummary,
+        LayerState,
+        LayerStatus,
+        RescanError,
+        RescanReport,
+        ShareRecord,
+    )),
+    modifiers(&AdminTokenAddon),
+)]
+pub(crate) struct AdminApiDoc;
+
+struct AdminTokenAddon;
+
+impl Modify for AdminTokenAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+#[get("/openapi.json")]
+pub(crate) async fn get_openapi_document() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[get("/openapi.json")]
+pub(crate) async fn get_admin_openapi_document() -> HttpResponse {
+    HttpResponse::Ok().json(AdminApiDoc::openapi())
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::models::layer::Layer;
+use crate::models::server::SandboxConfig;
+
+const SANDBOX_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/local/sbin:/usr/sbin:/sbin";
+
+/// Builds the process of an exec action, inside a bubblewrap sandbox unless it is disabled.
+///
+/// The sandbox has its own mount namespace with the system paths mounted read-only and only the
+/// directory of the layer writable, no network, a cleared environment, an unprivileged user and
+/// no capabilities.
+pub(crate) fn layer_command(
+    sandbox: &SandboxConfig,
+    layer: &Layer,
+    layer_dir: &Path,
+    working_dir: &Path,
+    program: &str,
+    args: &[String],
+    secret_file: Option<&Path>,
+) -> Result<Command, String> {
+    let layer_sandbox = match layer.sandbox.as_ref() {
+        Some(layer_sandbox) if sandbox.allow_layer_settings => Some(layer_sandbox),
+        Some(_) => {
+            log::warn!(
+                "Ignoring sandbox settings of layer {}, the server doesn't allow them",
+                layer.uuid
+            );
+            None
+        }
+        None => None,
+    };
+
+    if !sandbox.enabled || layer_sandbox.map(|s| !s.enabled).unwrap_or(false) {
+        log::warn!("Running command of layer {} without sandbox", layer.uuid);
+        let mut process = Command::new(program);
+        process.args(args).current_dir(working_dir);
+        return Ok(process);
+    }
+
+    let layer_dir = layer_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", layer_dir.display(), e))?;
+    let working_dir = working_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", working_dir.display(), e))?;
+    if !working_dir.starts_with(&layer_dir) {
+        return Err(format!(
+            "Working directory {} is outside of the layer directory {}",
+            working_dir.display(),
+            layer_dir.display()
+        ));
+    }
+
+    // bubblewrap passes its environment on, the secret may be added to it
+    let mut process = Command::new(&sandbox.program);
+    process
+        .env_clear()
+        .args(["--unshare-all", "--unshare-user"])
+        .args(["--uid", &sandbox.uid.to_string()])
+        .args(["--gid", &sandbox.gid.to_string()])
+        .args(["--cap-drop", "ALL"])
+        .args(["--die-with-parent", "--new-session"])
+        .args(["--setenv", "PATH", SANDBOX_PATH])
+        .arg("--setenv")
+        .arg("HOME")
+        .arg(&layer_dir)
+        .args(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"]);
+    if layer_sandbox.map(|s| s.network).unwrap_or(false) {
+        process.arg("--share-net");
+    }
+
+    let mut read_only_paths = sandbox.read_only_paths.clone();
+    if let Some(layer_sandbox) = layer_sandbox {
+        read_only_paths.extend(layer_sandbox.read_only_paths.iter().map(PathBuf::from));
+    }
+    for path in read_only_paths.iter() {
+        process.arg("--ro-bind-try").arg(path).arg(path);
+    }
+
+    if let Some(secret_file) = secret_file {
+        process.arg("--ro-bind").arg(secret_file).arg(secret_file);
+    }
+
+    process
+        .arg("--bind")
+        .arg(&layer_dir)
+        .arg(&layer_dir)
+        .arg("--chdir")
+        .arg(&working_dir)
+        .arg("--")
+        .arg(program)
+        .args(args);
+    Ok(process)
+}
+
+/// Finds the bubblewrap executable like the shell would.
+pub(crate) fn find_sandbox_program(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,156 @@
+use std::ffi::CString;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread::{self, JoinHandle};
+
+use crate::models::layer::SecretDelivery;
+
+/// Secret handed to the process of an exec action, removed when dropped.
+pub(crate) struct SecretHandoff {
+    // private directory of the secret file or named pipe
+    dir: Option<PathBuf>,
+    file: Option<PathBuf>,
+    // writes the secret once the process opens the named pipe
+    pipe_writer: Option<JoinHandle<io::Result<()>>>,
+    // read end of a pipe already holding the secret, passed to the process as `fd`
+    read_fd: Option<(OwnedFd, i32)>,
+    env: Option<(String, String)>,
+}
+
+impl SecretHandoff {
+    /// Prepares the secret for a process, `secret_dir` should be on a tmpfs.
+    pub(crate) fn prepare(
+        delivery: &SecretDelivery,
+        secret: &str,
+        secret_dir: &Path,
+    ) -> io::Result<SecretHandoff> {
+        let mut handoff = SecretHandoff {
+            dir: None,
+            file: None,
+            pipe_writer: None,
+            read_fd: None,
+            env: None,
+        };
+        match delivery {
+            SecretDelivery::None | SecretDelivery::Stdin => {}
+            SecretDelivery::File => {
+                let file = handoff.create_dir(secret_dir)?.join("secret");
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&file)?
+                    .write_all(secret.as_bytes())?;
+                handoff.file = Some(file);
+            }
+            SecretDelivery::Pipe => {
+                let file = handoff.create_dir(secret_dir)?.join("secret");
+                let path = CString::new(file.as_os_str().as_bytes())?;
+                // SAFETY: path is a valid null-terminated string
+                if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let fifo = file.clone();
+                let secret = secret.to_string();
+                handoff.pipe_writer = Some(thread::spawn(move || {
+                    // blocks until the process opens the pipe for reading
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&fifo)?
+                        .write_all(secret.as_bytes())
+                }));
+                handoff.file = Some(file);
+            }
+            SecretDelivery::Env { name } => {
+                if name.is_empty() || name.contains('=') {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid environment variable name \"{}\"", name),
+                    ));
+                }
+                handoff.env = Some((name.clone(), secret.to_string()));
+            }
+            SecretDelivery::Fd { fd } => {
+                if *fd < 3 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("File descriptor {} is reserved for stdio", fd),
+                    ));
+                }
+                let mut fds = [0; 2];
+                // SAFETY: fds has room for both ends of the pipe
+                if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // SAFETY: both descriptors were just created and are owned by nobody else
+                let (read_fd, write_fd) =
+                    unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+                // the pipe buffer holds far more than a secret, closing the write end signals EOF
+                File::from(write_fd).write_all(secret.as_bytes())?;
+                handoff.read_fd = Some((read_fd, *fd));
+            }
+        }
+        Ok(handoff)
+    }
+
+    fn create_dir(&mut self, secret_dir: &Path) -> io::Result<PathBuf> {
+        let dir = secret_dir.join(format!("perimetr-{:016x}", rand::random::<u64>()));
+        DirBuilder::new().mode(0o700).create(&dir)?;
+        self.dir = Some(dir.clone());
+        Ok(dir)
+    }
+
+    /// Path of the secret file or named pipe, {secret_file} in the arguments.
+    pub(crate) fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Passes the environment variable or file descriptor to the process.
+    pub(crate) fn apply(&self, process: &mut Command) {
+        if let Some((name, secret)) = self.env.as_ref() {
+            process.env(name, secret);
+        }
+        if let Some((read_fd, fd)) = self.read_fd.as_ref() {
+            let (read_fd, fd) = (read_fd.as_raw_fd(), *fd);
+            // SAFETY: only async-signal-safe functions are called between fork and exec
+            unsafe {
+                process.pre_exec(move || {
+                    if read_fd == fd {
+                        // dup2 keeps close-on-exec of the same descriptor
+                        if libc::fcntl(fd, libc::F_SETFD, 0) != 0 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    } else if libc::dup2(read_fd, fd) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+}
+
+impl Drop for SecretHandoff {
+    fn drop(&mut self) {
+        if let (Some(writer), Some(file)) = (self.pipe_writer.take(), self.file.as_ref()) {
+            // unblocks the writer if the process never opened the pipe
+            let reader = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(file);
+            let _ = writer.join();
+            drop(reader);
+        }
+        if let Some(dir) = self.dir.take() {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                log::error!("Failed to remove secret directory {}: {}", dir.display(), e);
+            }
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::web;
+use serde_json::json;
+
+use crate::database::Storage;
+use crate::models::job::JobState;
+use crate::models::layer::{Layer, LayerState};
+use crate::services::actions::secure_delete;
+use crate::services::audit::{record, ACTOR_SERVER};
+use crate::services::layer::{record_state_change, scratch_dir};
+use crate::Configuration;
+
+/// Decryption jobs in flight, waited for before the server stops
+pub(crate) struct Decryptions {
+    draining: AtomicBool,
+    running: Mutex<HashMap<i32, RunningDecryption>>,
+}
+
+struct RunningDecryption {
+    config: Configuration,
+    filepath: PathBuf,
+    // process of the command the job is running, killed when the job is interrupted
+    process: Option<Child>,
+    interrupted: bool,
+}
+
+const INTERRUPTED: &str = "Interrupted by the shutdown of the server";
+
+/// End of the grace period reserved for killing the programs of running decryptions
+pub(crate) const INTERRUPT_PERIOD: Duration = Duration::from_secs(10);
+
+/// Removes a job from the running decryptions when it is finished
+pub(crate) struct DecryptionGuard {
+    decryptions: web::Data<Decryptions>,
+    job_id: i32,
+}
+
+impl Decryptions {
+    pub(crate) fn new() -> Decryptions {
+        Decryptions {
+            draining: AtomicBool::new(false),
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the server is shutting down and refuses new shares and decryptions
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn drain(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn running(&self) -> usize {
+        self.running.lock().unwrap().len()
+    }
+
+    pub(crate) fn track(
+        decryptions: &web::Data<Decryptions>,
+        job_id: i32,
+        config: &Configuration,
+        filepath: &Path,
+    ) -> DecryptionGuard {
+        decryptions.running.lock().unwrap().insert(
+            job_id,
+            RunningDecryption {
+                config: config.clone(),
+                filepath: filepath.to_path_buf(),
+                process: None,
+                interrupted: false,
+            },
+        );
+        DecryptionGuard {
+            decryptions: decryptions.clone(),
+            job_id,
+        }
+    }
+
+    /// Waits for the running decryptions and returns whether all of them finished in time.
+    pub(crate) async fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.running() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            actix_rt::time::sleep(Duration::from_millis(250)).await;
+        }
+        true
+    }
+
+    /// Whether a decryption of the layer file is running, its state must be left alone until it finishes
+    pub(crate) fn is_decrypting(&self, filepath: &Path) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .values()
+            .any(|decryption| decryption.filepath == filepath)
+    }
+
+    /// Whether a job was interrupted and must leave its result and the layer state alone
+    pub(crate) fn is_interrupted(&self, job_id: i32) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .get(&job_id)
+            .is_some_and(|decryption| decryption.interrupted)
+    }
+
+    /// Hands the process of a command over, so it can be killed if the job is interrupted.
+    pub(crate) fn register_process(&self, job_id: i32, mut process: Child) -> io::Result<()> {
+        if let Some(decryption) = self
+            .running
+            .lock()
+            .unwrap()
+            .get_mut(&job_id)
+            .filter(|decryption| !decryption.interrupted)
+        {
+            decryption.process = Some(process);
+            return Ok(());
+        }
+        // reaped outside of the lock, the process has just been killed
+        let _ = process.kill();
+        process.wait()?;
+        Err(io::Error::new(io::ErrorKind::Interrupted, INTERRUPTED))
+    }
+
+    /// Waits for the registered process of a job to exit, without blocking the executor.
+    pub(crate) async fn wait_process(&self, job_id: i32) -> io::Result<ExitStatus> {
+        loop {
+            {
+                let mut running = self.running.lock().unwrap();
+                let process = running
+                    .get_mut(&job_id)
+                    .and_then(|decryption| decryption.process.as_mut())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::Interrupted, INTERRUPTED))?;
+                if let Some(status) = process.try_wait()? {
+                    running.get_mut(&job_id).unwrap().process = None;
+                    return Ok(status);
+                }
+            }
+            actix_rt::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Kills the commands of decryptions that didn't finish in time and waits until the deadline
+    /// for their jobs to stop. Then records them as failed, removes their intermediate files and
+    /// unlocks their layers, so they can be decrypted again after the restart.
+    pub(crate) async fn interrupt(&self, storage: &dyn Storage, deadline: Instant) {
+        let mut processes = Vec::new();
+        let interrupted: Vec<(i32, Configuration, PathBuf)> = {
+            let mut running = self.running.lock().unwrap();
+            running
+                .iter_mut()
+                .map(|(job_id, decryption)| {
+                    log::warn!("Interrupting decryption job {}", job_id);
+                    decryption.interrupted = true;
+                    processes.extend(decryption.process.take().map(|process| (*job_id, process)));
+                    (
+                        *job_id,
+                        decryption.config.clone(),
+                        decryption.filepath.clone(),
+                    )
+                })
+                .collect()
+        };
+
+        // killed and reaped outside of the lock, the jobs stop once their process is gone
+        for (job_id, mut process) in processes {
+            let _ = process.kill();
+            loop {
+                match process.try_wait() {
+                    Ok(Some(_)) => break,
+                    Ok(None) if Instant::now() < deadline => {
+                        actix_rt::time::sleep(Duration::from_millis(100)).await
+                    }
+                    Ok(None) => {
+                        log::error!("Process of job {} didn't exit after being killed", job_id);
+                        break;
+                    }
+                    Err(e) => {
+                        log::error!("Failed to reap process of job {}: {}", job_id, e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // built-in actions can't be killed, jobs still running at the deadline are recorded as
+        // interrupted anyway and leave their result alone when they finish
+        loop {
+            let leftover: Vec<i32> = self
+                .running
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|job_id| interrupted.iter().any(|(id, _, _)| id == *job_id))
+                .copied()
+                .collect();
+            if leftover.is_empty() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                log::warn!(
+                    "Decryption job(s) {:?} didn't stop in time, recording them as interrupted",
+                    leftover
+                );
+                break;
+            }
+            actix_rt::time::sleep(Duration::from_millis(250)).await;
+        }
+
+        for (job_id, config, filepath) in interrupted {
+            let error = INTERRUPTED.to_string();
+            if let Err(e) = storage
+                .finish_job(job_id, JobState::Failed, Some(error.clone()))
+                .await
+            {
+                log::error!("Failed to store result of job {}: {}", job_id, e);
+            }
+
+            let layer_dir = filepath.parent().unwrap_or(".".as_ref());
+            let scratch_dir = scratch_dir(layer_dir, job_id);
+            if scratch_dir.exists() {
+                if let Err(e) = secure_delete(&scratch_dir) {
+                    log::error!(
+                        "Failed to remove scratch directory {}: {}",
+                        scratch_dir.display(),
+                        e
+                    );
+                }
+            }
+
+            let mut layer = match Layer::read_metadata(&filepath) {
+                Ok(layer) => layer,
+                Err(e) => {
+                    log::error!("Failed to read {}: {}", filepath.display(), e);
+                    continue;
+                }
+            };
+            record(
+                storage,
+                &config,
+                "decryption_finished",
+                Some(&layer.uuid),
+                ACTOR_SERVER,
+                json!({
+                    "job_id": job_id,
+                    "state": JobState::Failed.as_str(),
+                    "error": error,
+                }),
+            )
+            .await;
+            if layer.state == LayerState::Decrypting {
+                layer.state = LayerState::Idle;
+                if let Err(e) = layer.write_metadata(&filepath) {
+                    log::error!("Failed to unlock {}: {}", filepath.display(), e);
+                    continue;
+                }
+                record_state_change(storage, &config, &layer).await;
+            }
+        }
+    }
+}
+
+impl Drop for DecryptionGuard {
+    fn drop(&mut self) {
+        self.decryptions
+            .running
+            .lock()
+            .unwrap()
+            .remove(&self.job_id);
+    }
+}
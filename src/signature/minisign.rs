@@ -0,0 +1,224 @@
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+use crate::signature::{
+    normalize_fingerprint, split_inline, Signed, VerificationError, Verified, MINISIGN_COMMENT,
+};
+
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+struct PublicKey {
+    id: [u8; 8],
+    key: VerifyingKey,
+}
+
+/// Verifies a minisign or signify signature, accepting the key IDs of `signers` only unless it
+/// is empty.
+///
+/// Public keys are given as in `minisign -P` or the second line of their .pub file.
+pub(crate) fn verify(
+    message: &Signed,
+    public_keys: &[String],
+    signers: &[String],
+) -> Result<Verified, VerificationError> {
+    let (data, signature, text) = match message {
+        Signed::Inline(text) => {
+            let (data, signature) = split_inline(text, MINISIGN_COMMENT)?;
+            let text = String::from_utf8_lossy(&data).to_string();
+            (data, signature, text)
+        }
+        Signed::Detached {
+            data,
+            signature,
+            text,
+        } => (data.clone(), signature.clone(), text.clone()),
+    };
+    let public_keys = public_keys
+        .iter()
+        .map(|public_key| parse_public_key(public_key))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(VerificationError::Keys)?;
+
+    // untrusted comment, signature, then optionally trusted comment and its global signature
+    let mut lines = signature.lines().map(str::trim).skip(1);
+    let signature = lines
+        .next()
+        .and_then(|line| base64::decode(line).ok())
+        .filter(|signature| signature.len() == 74)
+        .ok_or_else(|| VerificationError::Malformed("Invalid minisign signature".into()))?;
+    let trusted_comment = lines.next();
+    let global_signature = lines.next();
+
+    let (algorithm, id, signature) = (&signature[..2], &signature[2..10], &signature[10..]);
+    let public_key = public_keys
+        .iter()
+        .find(|public_key| public_key.id == id)
+        .ok_or(VerificationError::NoValidSignature)?;
+
+    // signatures of minisign 0.8 and later are of the BLAKE2b-512 hash of the data
+    let signed = match algorithm {
+        b"Ed" => data,
+        b"ED" => Blake2b512::digest(&data).to_vec(),
+        _ => {
+            return Err(VerificationError::Malformed(
+                "Unknown minisign signature algorithm".into(),
+            ))
+        }
+    };
+    if !verify_ed25519(&public_key.key, &signed, signature) {
+        return Err(VerificationError::NoValidSignature);
+    }
+
+    // the trusted comment is signed together with the signature
+    if let (Some(trusted_comment), Some(global_signature)) = (trusted_comment, global_signature) {
+        let comment = trusted_comment
+            .strip_prefix(TRUSTED_COMMENT)
+            .ok_or_else(|| VerificationError::Malformed("Invalid trusted comment".into()))?;
+        let global_signature = base64::decode(global_signature)
+            .map_err(|e| VerificationError::Malformed(e.to_string()))?;
+        let mut signed = signature.to_vec();
+        signed.extend_from_slice(comment.as_bytes());
+        if !verify_ed25519(&public_key.key, &signed, &global_signature) {
+            return Err(VerificationError::NoValidSignature);
+        }
+    }
+
+    let key_id = format_key_id(&public_key.id);
+    if !signers.is_empty()
+        && !signers
+            .iter()
+            .any(|signer| normalize_fingerprint(signer) == key_id)
+    {
+        return Err(VerificationError::SignerNotPinned {
+            fingerprint: key_id,
+        });
+    }
+    Ok(Verified {
+        text,
+        signer: key_id,
+    })
+}
+
+fn parse_public_key(public_key: &str) -> Result<PublicKey, String> {
+    let encoded = public_key
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with(MINISIGN_COMMENT))
+        .ok_or("Empty minisign public key")?;
+    let bytes = base64::decode(encoded)
+        .map_err(|e| format!("Invalid minisign public key \"{}\": {}", encoded, e))?;
+    if bytes.len() != 42 || &bytes[..2] != b"Ed" {
+        return Err(format!(
+            "\"{}\" isn't a minisign or signify public key",
+            encoded
+        ));
+    }
+    let mut id = [0; 8];
+    id.copy_from_slice(&bytes[2..10]);
+    let mut key = [0; 32];
+    key.copy_from_slice(&bytes[10..]);
+    Ok(PublicKey {
+        id,
+        key: VerifyingKey::from_bytes(&key)
+            .map_err(|e| format!("Invalid minisign public key \"{}\": {}", encoded, e))?,
+    })
+}
+
+fn verify_ed25519(key: &VerifyingKey, data: &[u8], signature: &[u8]) -> bool {
+    match Signature::from_slice(signature) {
+        Ok(signature) => key.verify(data, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+// minisign shows the ID as little-endian number
+fn format_key_id(id: &[u8; 8]) -> String {
+    id.iter().rev().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn public_key(signing_key: &SigningKey) -> String {
+        let mut bytes = b"Ed".to_vec();
+        bytes.extend_from_slice(&ID);
+        bytes.extend_from_slice(signing_key.verifying_key().as_bytes());
+        format!(
+            "untrusted comment: minisign public key\n{}",
+            base64::encode(bytes)
+        )
+    }
+
+    // as minisign -S signs, with a BLAKE2b-512 prehash and a trusted comment
+    fn sign(signing_key: &SigningKey, data: &str, comment: &str) -> String {
+        let signature = signing_key.sign(&Blake2b512::digest(data.as_bytes()));
+        let mut bytes = b"ED".to_vec();
+        bytes.extend_from_slice(&ID);
+        bytes.extend_from_slice(&signature.to_bytes());
+        let mut global = signature.to_bytes().to_vec();
+        global.extend_from_slice(comment.as_bytes());
+        format!(
+            "{}{} signature from minisign secret key\n{}\n{}{}\n{}\n",
+            data,
+            MINISIGN_COMMENT,
+            base64::encode(bytes),
+            TRUSTED_COMMENT,
+            comment,
+            base64::encode(signing_key.sign(&global).to_bytes())
+        )
+    }
+
+    #[test]
+    fn parse_public_key_reads_the_key_id() {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let public_key = parse_public_key(&public_key(&signing_key)).unwrap();
+        assert_eq!(format_key_id(&public_key.id), "0807060504030201");
+        assert!(parse_public_key("").is_err());
+        assert!(parse_public_key("RWQ=").is_err());
+    }
+
+    #[test]
+    fn verify_accepts_signatures_of_the_public_keys() {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let message = Signed::Inline(sign(&signing_key, "2026-10-18T12:00:00Z\n", "ts"));
+        let public_keys = vec![public_key(&signing_key)];
+
+        let verified = verify(&message, &public_keys, &[]).unwrap();
+        assert_eq!(verified.text, "2026-10-18T12:00:00Z\n");
+        assert_eq!(verified.signer, "0807060504030201");
+        assert!(verify(&message, &public_keys, &["0807060504030201".to_string()]).is_ok());
+        assert!(matches!(
+            verify(&message, &public_keys, &["0000000000000000".to_string()]),
+            Err(VerificationError::SignerNotPinned { .. })
+        ));
+    }
+
+    #[test]
+    fn verify_rejects_modified_data_and_comments() {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let public_keys = vec![public_key(&signing_key)];
+        let signed = sign(&signing_key, "2026-10-18T12:00:00Z\n", "ts");
+
+        let modified = Signed::Inline(signed.replacen("12:00", "13:00", 1));
+        assert!(matches!(
+            verify(&modified, &public_keys, &[]),
+            Err(VerificationError::NoValidSignature)
+        ));
+        let modified = Signed::Inline(signed.replace("trusted comment: ts", "trusted comment: x"));
+        assert!(matches!(
+            verify(&modified, &public_keys, &[]),
+            Err(VerificationError::NoValidSignature)
+        ));
+
+        let other_key = SigningKey::from_bytes(&[4; 32]);
+        let message = Signed::Inline(signed);
+        assert!(matches!(
+            verify(&message, &[public_key(&other_key)], &[]),
+            Err(VerificationError::NoValidSignature)
+        ));
+    }
+}
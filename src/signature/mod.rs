@@ -0,0 +1,116 @@
+pub(crate) mod minisign;
+pub(crate) mod openpgp;
+pub(crate) mod ssh;
+
+use std::fmt;
+
+use crate::cleartext::MESSAGE_HEADER as PGP_SIGNED_MESSAGE_HEADER;
+use crate::models::dms::SignatureFormat;
+
+use openpgp::Keyring;
+
+const SSH_SIGNATURE_HEADER: &str = "-----BEGIN SSH SIGNATURE-----";
+const MINISIGN_COMMENT: &str = "untrusted comment:";
+
+/// Why a signed timestamp was rejected
+#[derive(Debug)]
+pub(crate) enum VerificationError {
+    // the keyring, allowed signers or public keys couldn't be read or hold no usable key
+    Keys(String),
+    // no signature of the configured format or a broken one
+    Malformed(String),
+    // no accepted key made a valid signature
+    NoValidSignature,
+    // an accepted key signed, but it isn't pinned for the source
+    SignerNotPinned { fingerprint: String },
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::Keys(e) => write!(f, "Failed to load keys: {}", e),
+            VerificationError::Malformed(e) => write!(f, "Malformed signed message: {}", e),
+            VerificationError::NoValidSignature => {
+                write!(f, "No accepted key made a valid signature")
+            }
+            VerificationError::SignerNotPinned { fingerprint } => write!(
+                f,
+                "Signed by key {}, which isn't pinned for the source",
+                fingerprint
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// Signed message fetched from a source
+pub(crate) enum Signed {
+    // text with its signature, clearsigned or followed by an SSH signature or minisign signature
+    Inline(String),
+    // armored detached signature of data, `text` is what the data states, f.e. the time of a commit
+    Detached {
+        data: Vec<u8>,
+        signature: String,
+        text: String,
+    },
+}
+
+/// Text of a message with a valid signature
+pub(crate) struct Verified {
+    pub(crate) text: String,
+    // fingerprint of the OpenPGP primary key or SSH key, or ID of the minisign key
+    pub(crate) signer: String,
+}
+
+/// Verifies a message in the signature format of its source, accepting `signers` only unless it
+/// is empty.
+pub(crate) fn verify(
+    message: &Signed,
+    format: &SignatureFormat,
+    keyring: Option<&Keyring>,
+    signers: &[String],
+) -> Result<Verified, VerificationError> {
+    match format {
+        SignatureFormat::Openpgp => keyring
+            .ok_or_else(|| VerificationError::Keys("pgp_keyring_file isn't configured".into()))?
+            .verify(message, signers),
+        SignatureFormat::Ssh {
+            allowed_signers,
+            namespace,
+        } => ssh::AllowedSigners::load(allowed_signers)?.verify(message, namespace, signers),
+        SignatureFormat::Minisign { public_keys } => {
+            minisign::verify(message, public_keys, signers)
+        }
+    }
+}
+
+/// Whether a text, f.e. a mail, contains a signature of any format.
+pub(crate) fn contains_signature(text: &str) -> bool {
+    text.contains(PGP_SIGNED_MESSAGE_HEADER)
+        || text.contains(SSH_SIGNATURE_HEADER)
+        || text.contains(MINISIGN_COMMENT)
+}
+
+// Signed data and signature of an inline message whose signature follows the data, which has to
+// be exactly what was signed
+fn split_inline(text: &str, signature_start: &str) -> Result<(Vec<u8>, String), VerificationError> {
+    let start = text
+        .find(signature_start)
+        .ok_or_else(|| VerificationError::Malformed(format!("No \"{}\"", signature_start)))?;
+    Ok((text.as_bytes()[..start].to_vec(), text[start..].to_string()))
+}
+
+// gpg prints fingerprints in groups of four, f.e. "ABCD 1234 …"
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = fingerprint.trim();
+    let fingerprint = fingerprint
+        .strip_prefix("0x")
+        .or_else(|| fingerprint.strip_prefix("0X"))
+        .unwrap_or(fingerprint);
+    fingerprint
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
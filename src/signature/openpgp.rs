@@ -0,0 +1,167 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use pgp::composed::{Deserializable, SignedPublicKey, StandaloneSignature};
+use pgp::packet::{PublicKey, PublicSubkey};
+use pgp::types::KeyTrait;
+
+use crate::cleartext::CleartextMessage;
+use crate::signature::{
+    normalize_fingerprint, Signed, VerificationError, Verified, PGP_SIGNED_MESSAGE_HEADER,
+};
+
+const ARMOR_HEADER: &[u8] = b"-----BEGIN PGP PUBLIC KEY BLOCK-----";
+const KEYBOX_MAGIC: &[u8] = b"KBXf";
+const SIGNATURE_FOOTER: &str = "-----END PGP SIGNATURE-----";
+
+/// OpenPGP public keys accepted for signed timestamps
+pub(crate) struct Keyring {
+    keys: Vec<SignedPublicKey>,
+}
+
+impl Keyring {
+    /// Loads armored or binary OpenPGP public keys, f.e. from `gpg --export --armor`.
+    pub(crate) fn load(path: &Path) -> Result<Keyring, VerificationError> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| VerificationError::Keys(format!("{}: {}", path.display(), e)))?;
+        if bytes.get(8..12) == Some(KEYBOX_MAGIC) {
+            return Err(VerificationError::Keys(format!(
+                "{} is a GnuPG keybox, export the keys with gpg --export --armor instead",
+                path.display()
+            )));
+        }
+
+        let parsed: Vec<_> = if bytes.starts_with(ARMOR_HEADER) {
+            SignedPublicKey::from_armor_many(Cursor::new(bytes))
+                .map_err(|e| VerificationError::Keys(e.to_string()))?
+                .0
+                .collect()
+        } else {
+            SignedPublicKey::from_bytes_many(Cursor::new(bytes)).collect()
+        };
+
+        let mut keys = Vec::new();
+        for key in parsed {
+            let key = key.map_err(|e| VerificationError::Keys(e.to_string()))?;
+            // keys with broken self-signatures would accept signatures of anyone
+            if let Err(e) = key.verify() {
+                println!(
+                    "Ignoring key {} with invalid self-signatures: {}",
+                    fingerprint(&key),
+                    e
+                );
+                continue;
+            }
+            keys.push(key);
+        }
+        if keys.is_empty() {
+            return Err(VerificationError::Keys(format!(
+                "{} contains no valid public key",
+                path.display()
+            )));
+        }
+        Ok(Keyring { keys })
+    }
+
+    /// Verifies a signed message, accepting keys of `signers` only unless it is empty.
+    ///
+    /// Fingerprints of `signers` may be of the primary key or of the signing subkey.
+    pub(crate) fn verify(
+        &self,
+        message: &Signed,
+        signers: &[String],
+    ) -> Result<Verified, VerificationError> {
+        match message {
+            Signed::Inline(text) => {
+                let message = clearsigned_part(text).ok_or_else(|| {
+                    VerificationError::Malformed("No clearsigned OpenPGP message".into())
+                })?;
+                let message =
+                    CleartextMessage::parse(&message).map_err(VerificationError::Malformed)?;
+                let signer = self.signer(
+                    signers,
+                    |key| message.verify(key),
+                    |subkey| message.verify(subkey),
+                )?;
+                Ok(Verified {
+                    text: message.text(),
+                    signer,
+                })
+            }
+            Signed::Detached {
+                data,
+                signature,
+                text,
+            } => {
+                let (signature, _) =
+                    StandaloneSignature::from_armor_single(Cursor::new(signature.as_bytes()))
+                        .map_err(|e| VerificationError::Malformed(e.to_string()))?;
+                let signer = self.signer(
+                    signers,
+                    |key| signature.verify(key, data).is_ok(),
+                    |subkey| signature.verify(subkey, data).is_ok(),
+                )?;
+                Ok(Verified {
+                    text: text.clone(),
+                    signer,
+                })
+            }
+        }
+    }
+
+    // fingerprint of the primary key of the first pinned key that made a valid signature
+    fn signer(
+        &self,
+        signers: &[String],
+        verify_key: impl Fn(&PublicKey) -> bool,
+        verify_subkey: impl Fn(&PublicSubkey) -> bool,
+    ) -> Result<String, VerificationError> {
+        let mut unpinned = None;
+        for key in self.keys.iter() {
+            let primary = fingerprint(key);
+            let signed_by = if verify_key(&key.primary_key) {
+                Some(primary.clone())
+            } else {
+                key.public_subkeys
+                    .iter()
+                    .find(|subkey| verify_subkey(&subkey.key))
+                    .map(|subkey| format_fingerprint(&subkey.key.fingerprint()))
+            };
+            let signed_by = match signed_by {
+                Some(signed_by) => signed_by,
+                None => continue,
+            };
+
+            let pinned = signers.is_empty()
+                || signers.iter().any(|signer| {
+                    let signer = normalize_fingerprint(signer);
+                    signer == primary || signer == signed_by
+                });
+            if !pinned {
+                // another signature of the message may be by a pinned key
+                unpinned.get_or_insert(primary);
+                continue;
+            }
+            return Ok(primary);
+        }
+        match unpinned {
+            Some(fingerprint) => Err(VerificationError::SignerNotPinned { fingerprint }),
+            None => Err(VerificationError::NoValidSignature),
+        }
+    }
+}
+
+fn fingerprint(key: &SignedPublicKey) -> String {
+    format_fingerprint(&key.primary_key.fingerprint())
+}
+
+fn format_fingerprint(fingerprint: &[u8]) -> String {
+    fingerprint.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// the clearsigned part of a longer text, f.e. of a mail
+fn clearsigned_part(text: &str) -> Option<String> {
+    let start = text.find(PGP_SIGNED_MESSAGE_HEADER)?;
+    let end = start + text[start..].find(SIGNATURE_FOOTER)? + SIGNATURE_FOOTER.len();
+    Some(text[start..end].replace("\r\n", "\n"))
+}
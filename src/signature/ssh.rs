@@ -0,0 +1,343 @@
+use std::path::Path;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use ssh_key::{HashAlg, PublicKey, SshSig};
+
+use crate::signature::{split_inline, Signed, VerificationError, Verified, SSH_SIGNATURE_HEADER};
+
+/// Keys of an allowed signers file of `ssh-keygen -Y verify`, see ssh-keygen(1)
+pub(crate) struct AllowedSigners {
+    signers: Vec<AllowedSigner>,
+}
+
+struct AllowedSigner {
+    principals: Vec<String>,
+    // all namespaces without the namespaces option
+    namespaces: Option<Vec<String>>,
+    valid_after: Option<DateTime<Utc>>,
+    valid_before: Option<DateTime<Utc>>,
+    key: PublicKey,
+}
+
+impl AllowedSigners {
+    pub(crate) fn load(path: &Path) -> Result<AllowedSigners, VerificationError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| VerificationError::Keys(format!("{}: {}", path.display(), e)))?;
+
+        let mut signers = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match parse_line(line) {
+                Ok(Some(signer)) => signers.push(signer),
+                Ok(None) => {}
+                Err(e) => {
+                    return Err(VerificationError::Keys(format!(
+                        "{}:{}: {}",
+                        path.display(),
+                        number + 1,
+                        e
+                    )))
+                }
+            }
+        }
+        if signers.is_empty() {
+            return Err(VerificationError::Keys(format!(
+                "{} contains no allowed signer",
+                path.display()
+            )));
+        }
+        Ok(AllowedSigners { signers })
+    }
+
+    /// Verifies a message, accepting principals or key fingerprints of `signers` only unless it
+    /// is empty.
+    pub(crate) fn verify(
+        &self,
+        message: &Signed,
+        namespace: &str,
+        signers: &[String],
+    ) -> Result<Verified, VerificationError> {
+        let (data, signature, text) = match message {
+            Signed::Inline(text) => {
+                let (data, signature) = split_inline(text, SSH_SIGNATURE_HEADER)?;
+                let text = String::from_utf8_lossy(&data).to_string();
+                (data, signature, text)
+            }
+            Signed::Detached {
+                data,
+                signature,
+                text,
+            } => (data.clone(), signature.clone(), text.clone()),
+        };
+        let signature = SshSig::from_pem(signature.trim())
+            .map_err(|e| VerificationError::Malformed(e.to_string()))?;
+        if signature.namespace() != namespace {
+            return Err(VerificationError::Malformed(format!(
+                "Signature is for namespace \"{}\" instead of \"{}\"",
+                signature.namespace(),
+                namespace
+            )));
+        }
+
+        let now = Utc::now();
+        let mut unpinned = None;
+        for signer in self.signers.iter() {
+            if let Some(namespaces) = signer.namespaces.as_ref() {
+                if !namespaces.iter().any(|allowed| allowed == namespace) {
+                    continue;
+                }
+            }
+            if signer.valid_after.map(|after| now < after).unwrap_or(false)
+                || signer
+                    .valid_before
+                    .map(|before| now > before)
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+            if signer.key.verify(namespace, &data, &signature).is_err() {
+                continue;
+            }
+
+            let fingerprint = signer.key.fingerprint(HashAlg::Sha256).to_string();
+            let pinned = signers.is_empty()
+                || signers.iter().any(|pinned| {
+                    *pinned == fingerprint || signer.principals.iter().any(|p| p == pinned)
+                });
+            if !pinned {
+                unpinned.get_or_insert(fingerprint);
+                continue;
+            }
+            return Ok(Verified {
+                text,
+                signer: fingerprint,
+            });
+        }
+        match unpinned {
+            Some(fingerprint) => Err(VerificationError::SignerNotPinned { fingerprint }),
+            None => Err(VerificationError::NoValidSignature),
+        }
+    }
+}
+
+// "principals [options] keytype key [comment]", lines with unsupported options are skipped
+fn parse_line(line: &str) -> Result<Option<AllowedSigner>, String> {
+    let tokens = split_outside_quotes(line, char::is_whitespace);
+    if tokens.len() < 3 {
+        return Err("Expected principals, key type and key".to_string());
+    }
+    let principals = split_outside_quotes(&unquote(&tokens[0]), |c| c == ',');
+
+    // the options are optional, keys start with their type
+    let (options, key) = match PublicKey::from_openssh(&tokens[1..].join(" ")) {
+        Ok(key) => (Vec::new(), key),
+        Err(_) => (
+            split_outside_quotes(&tokens[1], |c| c == ','),
+            PublicKey::from_openssh(&tokens[2..].join(" ")).map_err(|e| e.to_string())?,
+        ),
+    };
+
+    let mut signer = AllowedSigner {
+        principals,
+        namespaces: None,
+        valid_after: None,
+        valid_before: None,
+        key,
+    };
+    for option in options.iter() {
+        let (name, value) = option.split_once('=').unwrap_or((option, ""));
+        let value = unquote(value);
+        match name.to_ascii_lowercase().as_str() {
+            "namespaces" => {
+                signer.namespaces = Some(split_outside_quotes(&value, |c| c == ','));
+            }
+            "valid-after" => {
+                signer.valid_after = Some(
+                    parse_time(&value)
+                        .ok_or_else(|| format!("Invalid valid-after \"{}\"", value))?,
+                );
+            }
+            "valid-before" => {
+                signer.valid_before = Some(
+                    parse_time(&value)
+                        .ok_or_else(|| format!("Invalid valid-before \"{}\"", value))?,
+                );
+            }
+            _ => {
+                // f.e. cert-authority, accepting a line without its restriction would widen it
+                println!(
+                    "Ignoring allowed signer {} with unsupported option \"{}\"",
+                    signer.principals.join(","),
+                    name
+                );
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(signer))
+}
+
+fn split_outside_quotes(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        if !quoted && is_separator(c) {
+            if !current.is_empty() {
+                parts.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+fn unquote(text: &str) -> String {
+    text.trim_matches('"').to_string()
+}
+
+// YYYYMMDD[HHMM[SS]], in UTC with a Z suffix and in local time otherwise
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    let (value, utc) = match value.strip_suffix(['Z', 'z']) {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let time = match value.len() {
+        8 => NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+        12 => NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M").ok()?,
+        14 => NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S").ok()?,
+        _ => return None,
+    };
+    if utc {
+        Some(Utc.from_utc_datetime(&time))
+    } else {
+        Local
+            .from_local_datetime(&time)
+            .single()
+            .map(|time| time.with_timezone(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHyKcx4fr8bFZwDl4V4CSe8RIptVDIkg2gcpDP3kc9z3 test";
+
+    // ssh-keygen -Y sign -n perimetr-dms of "2026-10-18T12:00:00+00:00\n"
+    const SIGNED: &str = "2026-10-18T12:00:00+00:00
+-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAgfIpzHh+vxsVnAOXhXgJJ7xEim1
+UMiSDaBykM/eRz3PcAAAAMcGVyaW1ldHItZG1zAAAAAAAAAAZzaGE1MTIAAABTAAAAC3Nz
+aC1lZDI1NTE5AAAAQJjciSOPSad9Faw5JJZd5F6fPwxb7/glQi9Ff9fvvYjz4KFN7pprd+
+XTnBgEX25XKhc6IB2OuOOMwD4B8OJFrQ8=
+-----END SSH SIGNATURE-----
+";
+
+    fn allowed_signers(line: &str) -> AllowedSigners {
+        AllowedSigners {
+            signers: vec![parse_line(line).unwrap().unwrap()],
+        }
+    }
+
+    #[test]
+    fn parse_line_reads_principals_and_options() {
+        let signer = parse_line(&format!("owner@example.net,backup {}", KEY))
+            .unwrap()
+            .unwrap();
+        assert_eq!(signer.principals, vec!["owner@example.net", "backup"]);
+        assert!(signer.namespaces.is_none());
+
+        let signer = parse_line(&format!(
+            "owner@example.net namespaces=\"git,perimetr-dms\",valid-after=20240101Z {}",
+            KEY
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            signer.namespaces,
+            Some(vec!["git".to_string(), "perimetr-dms".to_string()])
+        );
+        assert_eq!(
+            signer.valid_after,
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_line_skips_unsupported_options() {
+        assert!(parse_line(&format!("*@example.net cert-authority {}", KEY))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn parse_line_rejects_malformed_lines() {
+        assert!(parse_line("owner@example.net ssh-ed25519").is_err());
+        assert!(parse_line("owner@example.net ssh-ed25519 AAAA").is_err());
+        assert!(parse_line(&format!("owner@example.net valid-before=tomorrow {}", KEY)).is_err());
+    }
+
+    #[test]
+    fn parse_time_reads_dates_and_times() {
+        assert_eq!(
+            parse_time("20240102030405Z"),
+            Some(Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap())
+        );
+        assert_eq!(
+            parse_time("202401020304z"),
+            Some(Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 0).unwrap())
+        );
+        assert!(parse_time("2024").is_none());
+        assert!(parse_time("20241301Z").is_none());
+    }
+
+    #[test]
+    fn verify_checks_namespace_and_signers() {
+        let message = Signed::Inline(SIGNED.to_string());
+        let signers = allowed_signers(&format!("owner@example.net {}", KEY));
+
+        let verified = signers.verify(&message, "perimetr-dms", &[]).unwrap();
+        assert_eq!(verified.text, "2026-10-18T12:00:00+00:00\n");
+        assert!(signers
+            .verify(&message, "perimetr-dms", &["owner@example.net".to_string()])
+            .is_ok());
+        assert!(matches!(
+            signers.verify(&message, "perimetr-dms", &["other".to_string()]),
+            Err(VerificationError::SignerNotPinned { .. })
+        ));
+        assert!(matches!(
+            signers.verify(&message, "git", &[]),
+            Err(VerificationError::Malformed(_))
+        ));
+
+        let restricted = allowed_signers(&format!("owner@example.net namespaces=\"git\" {}", KEY));
+        assert!(matches!(
+            restricted.verify(&message, "perimetr-dms", &[]),
+            Err(VerificationError::NoValidSignature)
+        ));
+    }
+
+    #[test]
+    fn verify_rejects_modified_text() {
+        let message = Signed::Inline(SIGNED.replacen("12:00", "13:00", 1));
+        let signers = allowed_signers(&format!("owner@example.net {}", KEY));
+        assert!(matches!(
+            signers.verify(&message, "perimetr-dms", &[]),
+            Err(VerificationError::NoValidSignature)
+        ));
+    }
+}
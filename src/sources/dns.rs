@@ -0,0 +1,77 @@
+use std::error::Error;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::system_conf::read_system_conf;
+use trust_dns_resolver::Resolver;
+
+use crate::signature::{contains_signature, Signed};
+use crate::sources::Source;
+
+/// TXT record with the base64 encoded signed text, split into strings of up to 255 bytes
+pub(crate) struct DnsSource {
+    name: String,
+    resolver: Resolver,
+}
+
+impl DnsSource {
+    pub(crate) fn new(
+        name: &str,
+        resolver: Option<&str>,
+        timeout: Duration,
+    ) -> Result<DnsSource, Box<dyn Error>> {
+        let (config, mut options) = match resolver {
+            Some(resolver) => {
+                let address = match resolver.parse::<SocketAddr>() {
+                    Ok(address) => address,
+                    Err(_) => SocketAddr::new(
+                        resolver
+                            .parse::<IpAddr>()
+                            .map_err(|e| format!("Invalid resolver \"{}\": {}", resolver, e))?,
+                        53,
+                    ),
+                };
+                let name_servers =
+                    NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true);
+                (
+                    ResolverConfig::from_parts(None, vec![], name_servers),
+                    ResolverOpts::default(),
+                )
+            }
+            None => read_system_conf()?,
+        };
+        options.timeout = timeout;
+        options.attempts = 1;
+        Ok(DnsSource {
+            name: name.to_string(),
+            resolver: Resolver::new(config, options)?,
+        })
+    }
+}
+
+impl Source for DnsSource {
+    fn fetch(&self) -> Result<Vec<Signed>, Box<dyn Error>> {
+        // records have no order, each of them is a candidate
+        let mut signed = Vec::new();
+        for record in self.resolver.txt_lookup(self.name.as_str())?.iter() {
+            let encoded: Vec<u8> = record
+                .txt_data()
+                .iter()
+                .flat_map(|data| data.iter().copied())
+                .collect();
+            // other TXT records of the name, f.e. for domain verification, are skipped
+            let text = match base64::decode(&encoded) {
+                Ok(decoded) => String::from_utf8_lossy(&decoded).to_string(),
+                Err(_) => continue,
+            };
+            if contains_signature(&text) {
+                signed.push(Signed::Inline(text));
+            }
+        }
+        if signed.is_empty() {
+            return Err(format!("No TXT record of {} with a signed text", self.name).into());
+        }
+        Ok(signed)
+    }
+}
@@ -0,0 +1,92 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::signature::Signed;
+use crate::sources::Source;
+
+/// Signed text in a local file, or in the files of a directory, newest first,
+/// f.e. synchronized by another tool
+pub(crate) struct FileSource {
+    pub(crate) path: PathBuf,
+    pub(crate) suffix: Option<String>,
+}
+
+impl Source for FileSource {
+    fn fetch(&self) -> Result<Vec<Signed>, Box<dyn Error>> {
+        if !self.path.is_dir() {
+            return Ok(vec![Signed::Inline(fs::read_to_string(&self.path)?)]);
+        }
+
+        let mut files: Vec<(SystemTime, PathBuf)> = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            if let Some(suffix) = self.suffix.as_ref() {
+                if !entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(suffix.as_str())
+                {
+                    continue;
+                }
+            }
+            files.push((metadata.modified()?, entry.path()));
+        }
+        if files.is_empty() {
+            return Err(format!("No file in directory {}", self.path.display()).into());
+        }
+        files.sort_by_key(|(modified, _)| Reverse(*modified));
+
+        files
+            .into_iter()
+            .map(|(_, path)| Ok(Signed::Inline(fs::read_to_string(path)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn fetch_returns_the_files_of_a_directory_newest_first() {
+        let dir = std::env::temp_dir().join(format!("perimetr-file-source-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let now = SystemTime::now();
+        for (name, age) in [("old.asc", 60), ("new.asc", 0), ("other.txt", 30)] {
+            let path = dir.join(name);
+            fs::write(&path, name).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        let source = FileSource {
+            path: dir.clone(),
+            suffix: Some(".asc".to_string()),
+        };
+        let texts: Vec<String> = source
+            .fetch()
+            .unwrap()
+            .into_iter()
+            .map(|signed| match signed {
+                Signed::Inline(text) => text,
+                _ => panic!("Signed text isn't inline"),
+            })
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(texts, vec!["new.asc".to_string(), "old.asc".to_string()]);
+    }
+}
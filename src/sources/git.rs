@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::Command;
+
+use chrono::DateTime;
+
+use crate::models::dms::Challenge;
+use crate::signature::Signed;
+use crate::sources::Source;
+
+/// Signed commit a reference of a local repository points to, f.e. kept up to date by a cron job
+///
+/// The commit proves the time of its committer date, or answers a challenge with a
+/// `perimetr-dms-response` line in its message. Commits may be signed with OpenPGP or SSH keys,
+/// the namespace of SSH signatures is git.
+pub(crate) struct GitSource {
+    pub(crate) repository: PathBuf,
+    pub(crate) reference: String,
+}
+
+impl Source for GitSource {
+    fn fetch(&self) -> Result<Vec<Signed>, Box<dyn Error>> {
+        // would be read as an option by git
+        if self.reference.starts_with('-') {
+            return Err(format!("Invalid reference \"{}\"", self.reference).into());
+        }
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.repository)
+            .args(["cat-file", "commit", &self.reference])
+            .output()?;
+        if !output.status.success() {
+            return Err(format!(
+                "git cat-file returned exit status {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(vec![parse_commit(&String::from_utf8(output.stdout)?)?])
+    }
+}
+
+// The signature, OpenPGP or SSH, covers the commit object without its gpgsig header, whose
+// continuation lines start with a space
+fn parse_commit(commit: &str) -> Result<Signed, Box<dyn Error>> {
+    let (headers, message) = commit.split_once("\n\n").unwrap_or((commit, ""));
+
+    let mut payload = Vec::new();
+    let mut signature: Option<String> = None;
+    let mut in_signature = false;
+    let mut committed = None;
+    for line in headers.lines() {
+        if let Some(continuation) = line.strip_prefix(' ') {
+            match signature.as_mut() {
+                Some(signature) if in_signature => {
+                    signature.push('\n');
+                    signature.push_str(continuation);
+                }
+                _ => payload.push(line),
+            }
+            continue;
+        }
+        in_signature = false;
+
+        if let Some(value) = line
+            .strip_prefix("gpgsig ")
+            .or_else(|| line.strip_prefix("gpgsig-sha256 "))
+        {
+            signature = Some(value.to_string());
+            in_signature = true;
+            continue;
+        }
+        if let Some(committer) = line.strip_prefix("committer ") {
+            // "Name <mail> 1700000000 +0100"
+            let mut fields = committer.rsplitn(3, ' ');
+            let offset = fields.next().unwrap_or_default();
+            let seconds = fields.next().unwrap_or_default();
+            committed = Some(DateTime::parse_from_str(
+                &format!("{} {}", seconds, offset),
+                "%s %z",
+            )?);
+        }
+        payload.push(line);
+    }
+
+    let signature = signature.ok_or("Commit isn't signed")?;
+    let committed = committed.ok_or("Commit without committer")?;
+    let text = match message
+        .lines()
+        .find(|line| Challenge::is_response_line(line))
+    {
+        Some(response) => response.to_string(),
+        None => committed.to_rfc3339(),
+    };
+
+    let mut data = payload.join("\n");
+    data.push_str("\n\n");
+    data.push_str(message);
+    Ok(Signed::Detached {
+        data: data.into_bytes(),
+        signature,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNED_COMMIT: &str = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904
+author Owner <owner@example.net> 1700000000 +0100
+committer Owner <owner@example.net> 1700000060 +0100
+gpgsig -----BEGIN SSH SIGNATURE-----
+ U1NIU0lH
+ -----END SSH SIGNATURE-----
+
+alive
+";
+
+    fn detached(commit: &str) -> (String, String, String) {
+        match parse_commit(commit).unwrap() {
+            Signed::Detached {
+                data,
+                signature,
+                text,
+            } => (String::from_utf8(data).unwrap(), signature, text),
+            Signed::Inline(_) => panic!("Commit parsed as inline message"),
+        }
+    }
+
+    #[test]
+    fn parse_commit_separates_the_signature() {
+        let (data, signature, text) = detached(SIGNED_COMMIT);
+        assert_eq!(
+            data,
+            "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904
+author Owner <owner@example.net> 1700000000 +0100
+committer Owner <owner@example.net> 1700000060 +0100
+
+alive
+"
+        );
+        assert_eq!(
+            signature,
+            "-----BEGIN SSH SIGNATURE-----\nU1NIU0lH\n-----END SSH SIGNATURE-----"
+        );
+        assert_eq!(text, "2023-11-14T23:14:20+01:00");
+    }
+
+    #[test]
+    fn parse_commit_prefers_a_challenge_response() {
+        let commit = format!("{}perimetr-dms-response abc123\n", SIGNED_COMMIT);
+        let (_, _, text) = detached(&commit);
+        assert_eq!(text, "perimetr-dms-response abc123");
+    }
+
+    #[test]
+    fn parse_commit_keeps_continuations_of_other_headers() {
+        let commit = SIGNED_COMMIT.replace(
+            "\ngpgsig",
+            "\nmergetag object 1234\n type commit\ngpgsig-sha256",
+        );
+        let (data, signature, _) = detached(&commit);
+        assert!(data.contains("mergetag object 1234\n type commit\n"));
+        assert!(!data.contains("SSH SIGNATURE"));
+        assert!(signature.starts_with("-----BEGIN SSH SIGNATURE-----"));
+    }
+
+    #[test]
+    fn parse_commit_rejects_unsigned_commits() {
+        let unsigned = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904
+committer Owner <owner@example.net> 1700000060 +0100
+
+alive
+";
+        assert!(parse_commit(unsigned).is_err());
+    }
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+
+use crate::signature::Signed;
+use crate::sources::Source;
+
+/// Signed text served by a webserver, f.e. put there with scripts/dms-sign.sh
+pub(crate) struct HttpSource {
+    url: String,
+    client: Client,
+}
+
+impl HttpSource {
+    pub(crate) fn new(url: &str, timeout: Duration) -> Result<HttpSource, Box<dyn Error>> {
+        Ok(HttpSource {
+            url: url.to_string(),
+            client: Client::builder().timeout(timeout).build()?,
+        })
+    }
+}
+
+impl Source for HttpSource {
+    fn fetch(&self) -> Result<Vec<Signed>, Box<dyn Error>> {
+        let text = self
+            .client
+            .get(&self.url)
+            .send()?
+            .error_for_status()?
+            .text()?;
+        Ok(vec![Signed::Inline(text)])
+    }
+}
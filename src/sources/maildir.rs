@@ -0,0 +1,64 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::signature::{contains_signature, Signed};
+use crate::sources::Source;
+
+/// Mails with a signed text in a Maildir, f.e. delivered by fetchmail
+///
+/// The text must be the body of the mail, neither PGP/MIME nor quoted-printable encoded.
+pub(crate) struct MaildirSource {
+    pub(crate) path: PathBuf,
+    pub(crate) subject: Option<String>,
+}
+
+impl Source for MaildirSource {
+    fn fetch(&self) -> Result<Vec<Signed>, Box<dyn Error>> {
+        let mut mails: Vec<(SystemTime, PathBuf)> = Vec::new();
+        for dir in ["new", "cur"] {
+            for entry in fs::read_dir(self.path.join(dir))? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if metadata.is_file() {
+                    mails.push((metadata.modified()?, entry.path()));
+                }
+            }
+        }
+        mails.sort_by_key(|(modified, _)| Reverse(*modified));
+
+        let mut signed = Vec::new();
+        for (_, path) in mails {
+            // mails aren't necessarily UTF-8, the signed part is
+            let mail = String::from_utf8_lossy(&fs::read(&path)?).to_string();
+            if let Some(subject) = self.subject.as_ref() {
+                if !has_subject(&mail, subject) {
+                    continue;
+                }
+            }
+            let body = match mail.replace("\r\n", "\n").split_once("\n\n") {
+                Some((_, body)) => body.to_string(),
+                None => continue,
+            };
+            if contains_signature(&body) {
+                signed.push(Signed::Inline(body));
+            }
+        }
+        if signed.is_empty() {
+            return Err(format!("No mail with a signed text in {}", self.path.display()).into());
+        }
+        Ok(signed)
+    }
+}
+
+fn has_subject(mail: &str, subject: &str) -> bool {
+    mail.lines()
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            line.split_once(':')
+                .filter(|(name, _)| name.eq_ignore_ascii_case("subject"))
+        })
+        .any(|(_, value)| value.contains(subject))
+}
@@ -0,0 +1,46 @@
+pub(crate) mod dns;
+pub(crate) mod file;
+pub(crate) mod git;
+pub(crate) mod http;
+pub(crate) mod maildir;
+
+use std::error::Error;
+use std::time::Duration;
+
+use crate::models::dms::SourceTransport;
+use crate::signature::Signed;
+
+/// Transport a signed timestamp or challenge response is fetched over.
+pub(crate) trait Source {
+    /// Fetches the signed messages offered by the source, newest first. Anyone able to place a
+    /// message in a mailbox, directory or record set may offer one, the first that verifies counts.
+    fn fetch(&self) -> Result<Vec<Signed>, Box<dyn Error>>;
+}
+
+/// Creates the source of a configured transport, fetching gives up after `timeout`.
+pub(crate) fn source(
+    transport: &SourceTransport,
+    timeout: Duration,
+) -> Result<Box<dyn Source>, Box<dyn Error>> {
+    Ok(match transport {
+        SourceTransport::Http { url } => Box::new(http::HttpSource::new(url, timeout)?),
+        SourceTransport::File { path, suffix } => Box::new(file::FileSource {
+            path: path.clone(),
+            suffix: suffix.clone(),
+        }),
+        SourceTransport::Git {
+            repository,
+            reference,
+        } => Box::new(git::GitSource {
+            repository: repository.clone(),
+            reference: reference.clone(),
+        }),
+        SourceTransport::Maildir { path, subject } => Box::new(maildir::MaildirSource {
+            path: path.clone(),
+            subject: subject.clone(),
+        }),
+        SourceTransport::Dns { name, resolver } => {
+            Box::new(dns::DnsSource::new(name, resolver.as_deref(), timeout)?)
+        }
+    })
+}
@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{any_supported_type, CertifiedKey};
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls_pemfile::Item;
+
+/// Serves a certificate from PEM files and picks up renewed certificates without a restart.
+pub(crate) struct ReloadingCertResolver {
+    certificate: PathBuf,
+    private_key: PathBuf,
+    current: RwLock<(Arc<CertifiedKey>, Option<SystemTime>)>,
+}
+
+impl ReloadingCertResolver {
+    pub(crate) fn new(
+        certificate: &Path,
+        private_key: &Path,
+    ) -> Result<Arc<ReloadingCertResolver>, Box<dyn Error>> {
+        let modified = last_modified(certificate, private_key);
+        let certified_key = load_certified_key(certificate, private_key)?;
+
+        Ok(Arc::new(ReloadingCertResolver {
+            certificate: certificate.to_path_buf(),
+            private_key: private_key.to_path_buf(),
+            current: RwLock::new((Arc::new(certified_key), modified)),
+        }))
+    }
+
+    /// Checks the PEM files periodically and reloads them once they changed.
+    pub(crate) fn watch(self: Arc<Self>, interval: Duration) {
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            self.reload_if_changed();
+        });
+    }
+
+    fn reload_if_changed(&self) {
+        let modified = last_modified(&self.certificate, &self.private_key);
+        if modified.is_none() || self.current.read().map(|c| c.1 == modified).unwrap_or(true) {
+            return;
+        }
+
+        // keep serving the old certificate if the new one is broken, f.e. only half written
+        match load_certified_key(&self.certificate, &self.private_key) {
+            Ok(certified_key) => {
+                if let Ok(mut current) = self.current.write() {
+                    *current = (Arc::new(certified_key), modified);
+                    log::info!("Reloaded TLS certificate {}", self.certificate.display());
+                }
+            }
+            Err(e) => log::error!(
+                "Failed to reload TLS certificate {}: {}",
+                self.certificate.display(),
+                e
+            ),
+        }
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.current.read().ok().map(|current| current.0.clone())
+    }
+}
+
+pub(crate) fn rustls_config(resolver: Arc<ReloadingCertResolver>) -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver)
+}
+
+pub(crate) fn load_certified_key(
+    certificate: &Path,
+    private_key: &Path,
+) -> Result<CertifiedKey, Box<dyn Error>> {
+    let mut certificates = Vec::new();
+    for item in read_pem_items(certificate)? {
+        if let Item::X509Certificate(der) = item {
+            certificates.push(Certificate(der));
+        }
+    }
+    if certificates.is_empty() {
+        return Err(format!("No certificate found in {}", certificate.display()).into());
+    }
+
+    let key = read_pem_items(private_key)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::RSAKey(der) | Item::PKCS8Key(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| format!("No private key found in {}", private_key.display()))?;
+
+    Ok(CertifiedKey::new(certificates, any_supported_type(&key)?))
+}
+
+fn read_pem_items(path: &Path) -> Result<Vec<Item>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut items = Vec::new();
+    while let Some(item) = rustls_pemfile::read_one(&mut reader)? {
+        items.push(item);
+    }
+    Ok(items)
+}
+
+fn last_modified(certificate: &Path, private_key: &Path) -> Option<SystemTime> {
+    let certificate = fs::metadata(certificate).and_then(|m| m.modified()).ok()?;
+    let private_key = fs::metadata(private_key).and_then(|m| m.modified()).ok()?;
+    Some(certificate.max(private_key))
+}
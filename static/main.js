@@ -4,6 +4,15 @@ let layerselector = document.getElementById('layer');
 let shareinput = document.getElementById('share');
 let formresponse = document.getElementById('formresponse');
 let layerstatus = document.getElementById('layerstatus');
+let jobform = document.getElementById('jobform');
+let joblayerselector = document.getElementById('joblayer');
+let admintokeninput = document.getElementById('admintoken');
+let jobresponse = document.getElementById('jobresponse');
+let joblist = document.getElementById('joblist');
+let estatetitle = document.getElementById('estatetitle');
+let estatelogo = document.getElementById('estatelogo');
+let estatemessage = document.getElementById('estatemessage');
+let estatekeepers = document.getElementById('estatekeepers');
 
 function formMessage(message) {
         formresponse.className = '';
@@ -30,57 +39,208 @@ function formSubmitHandler(e) {
 
     shareinput.value = '';
 
-    fetch(`/layer/${uuid}/share`, {
+    fetch(`layer/${uuid}/share`, {
         method: 'POST',
         body: share,
     }).then(res => {
-        res.text().then(text => {
+        res.json().then(body => {
             if (res.ok) {
-                formMessage(text);
-                requestLayerStatus().catch(formError);
+                formMessage(body.message);
             } else {
-                    formError(`${res.statusText} (${text})`);
+                    formError(`${body.message} (${body.code})`);
             }
-        });
+        }).catch(() => formError(res.statusText));
     }).catch(formError).finally(() => {
         sharefieldset.removeAttribute('disabled');
     })
 }
 
-async function requestLayerStatus() {
-    return fetch('/layers').then(res => res.json()).then(layers => {
-        layerselector.options.length = 0;
-        layerstatus.innerHTML = '';
+function jobError(err) {
+        jobresponse.className = 'red';
+        jobresponse.textContent = `Error: ${err}`;
+}
+
+function renderOutput(title, text, truncated) {
+    let details = document.createElement('details');
+    let summary = document.createElement('summary');
+    summary.textContent = truncated ? `${title} (truncated)` : title;
+    let pre = document.createElement('pre');
+    pre.textContent = text || '(empty)';
+    details.appendChild(summary);
+    details.appendChild(pre);
+    return details;
+}
+
+function renderJobs(jobs) {
+    joblist.innerHTML = '';
+
+    if (jobs.length === 0) {
+        joblist.textContent = 'No decryption jobs found for this layer.';
+        return;
+    }
+
+    jobs.forEach(job => {
+        let section = document.createElement('section');
+        let heading = document.createElement('h4');
+        heading.textContent = `Job ${job.id}: ${job.state} (started ${job.started_at})`;
+        if (job.state == 'failed') {
+            heading.className = 'red';
+        }
+        section.appendChild(heading);
+
+        if (job.error) {
+            let error = document.createElement('p');
+            error.className = 'red';
+            error.textContent = job.error;
+            section.appendChild(error);
+        }
+
+        job.commands.forEach(command => {
+            let p = document.createElement('p');
+            // built-in actions and killed programs have no exit status
+            p.textContent = command.exit_status === null
+                ? command.program
+                : `${command.program}: exit status ${command.exit_status}`;
+            section.appendChild(p);
+            section.appendChild(renderOutput('stdout', command.stdout, command.stdout_truncated));
+            section.appendChild(renderOutput('stderr', command.stderr, command.stderr_truncated));
+        });
+
+        joblist.appendChild(section);
+    });
+}
+
+function jobSubmitHandler(e) {
+    e.preventDefault();
+
+    let uuid = joblayerselector.value;
+    let token = admintokeninput.value;
+
+    if (!uuid || !token) {
+        jobError('Please select a layer and enter the admin token.');
+        return;
+    }
+
+    jobresponse.className = '';
+    jobresponse.textContent = '';
+
+    fetch(`layer/${uuid}/jobs`, {
+        headers: { 'Authorization': `Bearer ${token}` },
+    }).then(res => {
+        if (res.ok) {
+            return res.json().then(renderJobs);
+        }
+        return res.json().then(body => jobError(body.message));
+    }).catch(jobError);
+}
+
+// layer status by uuid, kept up to date by the event stream
+let layers = new Map();
+let progress = new Map();
+
+function renderLayerStatus() {
+    let selectedlayer = layerselector.value;
+    let selectedjoblayer = joblayerselector.value;
+    layerselector.options.length = 0;
+    joblayerselector.options.length = 0;
+    layerstatus.innerHTML = '';
 
-        if (layers.length === 0) {
-            layerstatus.innerHTML = 'No layers found.';
-            return;
+    if (layers.size === 0) {
+        layerstatus.innerHTML = 'No layers found.';
+        return;
+    }
+
+    layers.forEach(layer => {
+        if (layer.state == 'idle') {
+            layerselector.appendChild(new Option(layer.uuid, layer.uuid, false, layer.uuid == selectedlayer));
         }
+        joblayerselector.appendChild(new Option(layer.uuid, layer.uuid, false, layer.uuid == selectedjoblayer));
+        let li = document.createElement('li');
+        let b = document.createElement('b');
+        b.textContent = layer.uuid;
+        li.appendChild(b);
+        let text = `: ${layer.state}, ${layer.shares_received} of ${layer.shares_required} share(s) received`;
+        if (layer.state == 'decrypting' && progress.has(layer.uuid)) {
+            let step = progress.get(layer.uuid);
+            text += ` (step ${step.step} of ${step.steps}: ${step.program})`;
+        }
+        li.appendChild(document.createTextNode(text));
+        layerstatus.appendChild(li);
+    });
+}
 
-        layers.forEach(layer => {
-            if (layer.state == 'idle') {
-                layerselector.appendChild(new Option(layer.uuid));
+function handleLayerEvent(event) {
+    let layer = layers.get(event.uuid);
+
+    switch (event.type) {
+        case 'snapshot':
+            layers = new Map(event.layers.map(layer => [layer.uuid, layer]));
+            progress.clear();
+            break;
+        case 'share_received':
+            if (layer) {
+                layer.shares_received = event.shares_received;
+                layer.shares_required = event.shares_required;
             }
-            let li = document.createElement('li');
-            let shares_needed = 1;
-            if (layer.state != 'idle') {
-                shares_needed = ' no more';
-            } else if (layer.vsss != null) {
-                shares_needed = layer.vsss.threshold;
+            break;
+        case 'state_changed':
+            if (layer) {
+                layer.state = event.state;
             }
-            li.innerHTML = `<b>${layer.uuid}</b>: ${layer.state}, ${shares_needed} share(s) required`;
-            layerstatus.appendChild(li);
-        })
-    })
+            if (event.state != 'decrypting') {
+                progress.delete(event.uuid);
+            }
+            break;
+        case 'decryption_progress':
+            progress.set(event.uuid, event);
+            break;
+        case 'job_finished':
+            progress.delete(event.uuid);
+            break;
+    }
+
+    renderLayerStatus();
 }
 
-async function liveRequestLayerStatus() {
-    await requestLayerStatus().catch(formError).finally(() => {
-        setTimeout(liveRequestLayerStatus, 10000);
+function subscribeLayerEvents() {
+    return new Promise(resolve => {
+        let events = new EventSource('events');
+        events.onmessage = message => {
+            handleLayerEvent(JSON.parse(message.data));
+            resolve();
+        };
+        // EventSource reconnects by itself and receives a fresh snapshot
+        events.onerror = () => formError('Lost connection to server, reconnecting…');
+        events.onopen = () => formMessage('');
     });
 }
 
-liveRequestLayerStatus().then(() => {
-   sharefieldset.removeAttribute('disabled'); 
+function renderEstate(estate) {
+    let title = estate.branding.title || estate.name;
+    document.title = title;
+    estatetitle.textContent = title;
+    if (estate.branding.logo_url) {
+        estatelogo.src = estate.branding.logo_url;
+        estatelogo.hidden = false;
+    }
+    if (estate.branding.message) {
+        estatemessage.textContent = estate.branding.message;
+        estatemessage.hidden = false;
+    }
+    if (estate.branding.accent_color) {
+        document.documentElement.style.setProperty('--accent', estate.branding.accent_color);
+    }
+    if (estate.keepers && estate.keepers.length > 0) {
+        estatekeepers.textContent = `Secret keepers: ${estate.keepers.join(', ')}`;
+        estatekeepers.hidden = false;
+    }
+}
+
+// API paths are relative, the page is served below /estates/{id}/ by servers hosting several estates
+fetch('estate').then(res => res.json()).then(renderEstate).catch(() => {});
+
+subscribeLayerEvents().then(() => {
+   sharefieldset.removeAttribute('disabled');
    shareform.addEventListener('submit', formSubmitHandler);
-});
\ No newline at end of file
+   jobform.addEventListener('submit', jobSubmitHandler);
+});